@@ -1,10 +1,29 @@
 use clap::arg_enum;
-use dnslogger::parse::dns::PacketPrinter;
-use log::{debug, info};
-use pcap::Capture;
+use dnslogger::parse::dns::{
+    DnsType, NameProtocol, Opcode, PacketPrinter, PoisoningDetector, Qr, QueryTracker,
+    ServiceInstance, ServiceInventory,
+};
+use log::{debug, info, warn};
+use pcap::{Capture, Linktype};
+use serde_derive::Serialize;
 use std::env;
 use structopt::StructOpt;
 
+/// The name service ports and GRE, VXLAN and GENEVE tunnels, the ports
+/// also behind VLAN and QinQ tags. Each `vlan` shifts the offsets for the
+/// rest of the filter, so the tagged alternatives are nested and come
+/// last.
+const DEFAULT_BPF: &str = "src port (53 or 137 or 5353 or 5355) or \
+                           proto 47 or udp dst port (4789 or 6081) or (vlan and \
+                           (src port (53 or 137 or 5353 or 5355) or \
+                           (vlan and src port (53 or 137 or 5353 or 5355))))";
+
+/// Queries kept to check their responses against.
+const OUTSTANDING_QUERIES: usize = 10_000;
+
+/// Hosts kept track of by the poisoning detector.
+const TRACKED_RESPONDERS: usize = 10_000;
+
 arg_enum! {
     #[derive(Debug)]
     enum OutputFormat {
@@ -13,6 +32,16 @@ arg_enum! {
     }
 }
 
+/// A JSON line that is not a dns message, told apart from those by its
+/// `type`.
+#[derive(Serialize)]
+struct Report<'a, T> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    report: &'a T,
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt()]
 pub struct Options {
@@ -39,8 +68,21 @@ pub struct Options {
     )]
     output_format: OutputFormat,
 
-    /// Set capture filter
-    #[structopt(default_value = "src port (53 or 5353 or 5355)")]
+    /// Only show messages with a question for this type (e.g. AAAA, HTTPS, TYPE65)
+    #[structopt(long = "qtype")]
+    qtype: Option<DnsType>,
+
+    /// Print the DNS-SD services announced over mDNS, as they change on an interface or after a pcap file
+    #[structopt(long = "dns-sd")]
+    dns_sd: bool,
+
+    /// Warn about hosts answering LLMNR or NBNS queries for this many names
+    #[structopt(long = "detect-poisoning")]
+    detect_poisoning: Option<usize>,
+
+    /// Set capture filter. The default also matches tunnels and VLAN and QinQ tagged packets, MPLS and PPPoE need
+    /// e.g. "mpls and src port 53" or "pppoes and src port 53"
+    #[structopt(raw(default_value = "DEFAULT_BPF"))]
     bpf_expression: String,
 }
 
@@ -63,12 +105,86 @@ fn setup_logging(loglevel: u8) {
     env_logger::init();
 }
 
-fn handle_packet(packet: &pcap::Packet, output_format: &OutputFormat) {
-    if let Some(packet_printer) = PacketPrinter::parse_packet(packet) {
-        debug!("{:#?}", packet_printer);
-        match output_format {
-            OutputFormat::Text => println!("{}", packet_printer),
-            OutputFormat::Json => println!("{}", packet_printer.to_json().unwrap()),
+/// Print the packet and return the DNS-SD instances it added or changed.
+fn handle_packet(
+    packet: &pcap::Packet,
+    linktype: Linktype,
+    output_format: &OutputFormat,
+    qtype: &Option<DnsType>,
+    inventory: &mut Option<ServiceInventory>,
+    detector: &mut Option<PoisoningDetector>,
+    tracker: &mut QueryTracker,
+) -> Vec<ServiceInstance> {
+    let mut services = Vec::new();
+    match PacketPrinter::parse_packet_message(packet, linktype) {
+        Ok(Some((mut packet_printer, dns_message))) => {
+            // mDNS and NBNS responses do not repeat the question.
+            let checked = matches!(
+                packet_printer.protocol(),
+                NameProtocol::Dns | NameProtocol::Llmnr
+            );
+            let flags = &dns_message.header.flags;
+            if checked && flags.opcode == Opcode::Query {
+                if let Some((src, dest)) = packet_printer.endpoints() {
+                    match flags.qr {
+                        Qr::Query => tracker.add_query(src, dest, &dns_message),
+                        Qr::Response => {
+                            let violations = tracker.check_response(src, dest, &dns_message);
+                            packet_printer.add_violations(violations);
+                        }
+                    }
+                }
+            }
+            if let Some(inventory) = inventory {
+                services = inventory.add_message(&dns_message);
+            }
+            let alert = match (detector, packet_printer.source()) {
+                (Some(detector), Some(src)) => {
+                    detector.add_message(packet_printer.protocol(), src, &dns_message)
+                }
+                _ => None,
+            };
+            if qtype
+                .as_ref()
+                .map_or(true, |qtype| packet_printer.has_qtype(qtype))
+            {
+                debug!("{:#?}", packet_printer);
+                match output_format {
+                    OutputFormat::Text => println!("{}", packet_printer),
+                    OutputFormat::Json => println!("{}", packet_printer.to_json().unwrap()),
+                }
+            }
+            if let Some(alert) = alert {
+                match output_format {
+                    OutputFormat::Text => println!("poisoning: {}", alert),
+                    OutputFormat::Json => {
+                        let report = Report {
+                            kind: "poisoning",
+                            report: &alert,
+                        };
+                        println!("{}", serde_json::to_string(&report).unwrap())
+                    }
+                }
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!(
+            "{}.{}: {}",
+            packet.header.ts.tv_sec, packet.header.ts.tv_usec, e
+        ),
+    }
+    services
+}
+
+fn print_service(service: &ServiceInstance, output_format: &OutputFormat) {
+    match output_format {
+        OutputFormat::Text => println!("dns-sd: {}", service),
+        OutputFormat::Json => {
+            let report = Report {
+                kind: "dns-sd",
+                report: service,
+            };
+            println!("{}", serde_json::to_string(&report).unwrap())
         }
     }
 }
@@ -77,13 +193,35 @@ fn main() {
     let opts = Options::from_args();
     setup_logging(opts.loglevel);
     debug!("{:?}", opts);
+    let mut inventory = if opts.dns_sd {
+        Some(ServiceInventory::new())
+    } else {
+        None
+    };
+    let mut detector = opts
+        .detect_poisoning
+        .map(|threshold| PoisoningDetector::new(threshold, TRACKED_RESPONDERS));
+    let mut tracker = QueryTracker::new(OUTSTANDING_QUERIES);
 
     if let Some(pcap_file) = opts.pcap_file {
         info!("using pcap file {}", pcap_file);
         let mut cap = Capture::from_file(pcap_file).unwrap();
         cap.filter(&opts.bpf_expression[..], true).unwrap();
+        let linktype = cap.get_datalink();
+        debug!("datalink {:?}", linktype);
         while let Ok(packet) = cap.next_packet() {
-            handle_packet(&packet, &opts.output_format);
+            handle_packet(
+                &packet,
+                linktype,
+                &opts.output_format,
+                &opts.qtype,
+                &mut inventory,
+                &mut detector,
+                &mut tracker,
+            );
+        }
+        for service in inventory.iter().flat_map(ServiceInventory::services) {
+            print_service(&service, &opts.output_format);
         }
     } else if let Some(interface) = opts.interface {
         info!("using interface {}", interface);
@@ -94,8 +232,21 @@ fn main() {
             .open()
             .unwrap();
         cap.filter(&opts.bpf_expression[..], true).unwrap();
+        let linktype = cap.get_datalink();
+        debug!("datalink {:?}", linktype);
         while let Ok(packet) = cap.next_packet() {
-            handle_packet(&packet, &opts.output_format);
+            let services = handle_packet(
+                &packet,
+                linktype,
+                &opts.output_format,
+                &opts.qtype,
+                &mut inventory,
+                &mut detector,
+                &mut tracker,
+            );
+            for service in services {
+                print_service(&service, &opts.output_format);
+            }
         }
     } else {
         eprintln!("Need an interface or a file. Se --help");
@@ -18,12 +18,16 @@
 //!                 let ipv4_packet = Ipv4Packet::new(ethernet.payload()).unwrap();
 //!                 if let IpNextHeaderProtocols::Udp = ipv4_packet.get_next_level_protocol() {
 //!                     let udp_packet = UdpPacket::new(ipv4_packet.payload()).unwrap();
-//!                     let (rest, dns_message) = dnslogger::parse::dns_message(
+//!                     match dnslogger::parse::dns_message(
 //!                         udp_packet.payload(),
 //!                         resolve_all_resource_records
-//!                     ).unwrap();
-//!                     println!("{:?}", dns_message);
-//!                     println!("{:02x?}", rest);
+//!                     ) {
+//!                         Ok((rest, dns_message)) => {
+//!                             println!("{:?}", dns_message);
+//!                             println!("{:02x?}", rest);
+//!                         }
+//!                         Err(e) => println!("malformed dns message: {}", e),
+//!                     }
 //!                 }
 //!             }
 //!             _ => println!("unhandled packet: {:?}", ethernet),
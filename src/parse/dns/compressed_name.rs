@@ -1,110 +1,176 @@
+use crate::parse::dns::*;
 use log::error;
 use nom::*;
+use serde_derive::Serialize;
 
-const RESOLVE_NAME_RECURSION_MAX: usize = 10;
-
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
-pub struct CompressedNameChain<'a> {
-    pub name: Option<String>,
-    tokens: Vec<CompressedName<'a>>,
+/// Why a domain name could not be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum NameError {
+    /// The name runs past the end of the message.
+    Truncated,
+    /// A label longer than 63 octets, or a length octet above 63 that is
+    /// not a compression pointer.
+    LabelTooLong(u8),
+    /// The name is longer than 255 octets.
+    NameTooLong,
+    /// A compression pointer past the end of the message.
+    PointerOutOfRange(u16),
+    /// A compression pointer to itself or a later position.
+    ForwardPointer(u16),
+    /// A compression pointer to an offset already visited for this name.
+    PointerLoop(u16),
+    /// An empty label in presentation format.
+    EmptyLabel,
+    /// A bad `\\` escape in presentation format.
+    BadEscape,
 }
 
-impl<'a> CompressedNameChain<'a> {
-    pub fn push(&mut self, val: CompressedName<'a>) {
-        self.tokens.push(val);
+impl std::fmt::Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameError::Truncated => write!(f, "name truncated"),
+            NameError::LabelTooLong(len) => write!(f, "bad label length 0x{:02x}", len),
+            NameError::NameTooLong => write!(f, "name longer than {} octets", NAME_MAX_LEN),
+            NameError::PointerOutOfRange(offs) => write!(f, "pointer {} out of range", offs),
+            NameError::ForwardPointer(offs) => write!(f, "forward pointer {}", offs),
+            NameError::PointerLoop(offs) => write!(f, "pointer loop at {}", offs),
+            NameError::EmptyLabel => write!(f, "empty label"),
+            NameError::BadEscape => write!(f, "bad escape"),
+        }
     }
+}
 
-    pub fn resolve_name(&mut self, recursion: usize, full_dns_message: &[u8]) -> &Option<String> {
-        if recursion >= RESOLVE_NAME_RECURSION_MAX {
-            error!("recursion exceeded!");
-            return &None;
-        }
-        let recursion = recursion + 1;
-        match &self.name {
-            None => {
-                let mut values: Vec<String> = Vec::new();
-                for token in self.tokens.iter() {
-                    match token {
-                        CompressedName::Label(name) => values.push((*name).to_string()),
-                        CompressedName::Pointer(offs) => {
-                            let offs = *offs as usize;
-                            if let Ok((_, mut name_chain)) =
-                                parse_compressed_chain(&full_dns_message[offs..])
-                            {
-                                if let Some(full_name) =
-                                    name_chain.resolve_name(recursion, full_dns_message)
-                                {
-                                    values.push(full_name.to_string());
-                                }
-                            } else {
-                                error!("failed to parse_qname_chain: offs: {}", offs);
-                            }
-                        }
-                    }
+/// Decompress the name starting at `offset` in `full_dns_message`.
+///
+/// Every pointer must point before itself and each target may only be
+/// visited once, so hostile messages can not make this loop.
+pub(crate) fn decompress_name(
+    full_dns_message: &[u8],
+    offset: usize,
+) -> Result<Vec<&[u8]>, DnsParseError> {
+    let mut labels = Vec::new();
+    let mut visited: Vec<u64> = Vec::new();
+    let mut name_len = 1;
+    let mut pos = offset;
+    loop {
+        let len = *full_dns_message
+            .get(pos)
+            .ok_or_else(|| DnsParseError::invalid_name(pos, NameError::Truncated))?;
+        match len & 0xc0 {
+            0x00 if len == 0 => return Ok(labels),
+            0x00 => {
+                let len = len as usize;
+                name_len += len + 1;
+                if name_len > NAME_MAX_LEN {
+                    return Err(DnsParseError::invalid_name(pos, NameError::NameTooLong));
                 }
-                self.name = Some(values.join("."));
-                &self.name
+                let label = full_dns_message
+                    .get(pos + 1..pos + 1 + len)
+                    .ok_or_else(|| DnsParseError::invalid_name(pos, NameError::Truncated))?;
+                labels.push(label);
+                pos += 1 + len;
+            }
+            0xc0 => {
+                let low = *full_dns_message
+                    .get(pos + 1)
+                    .ok_or_else(|| DnsParseError::invalid_name(pos, NameError::Truncated))?;
+                let target = (u16::from(len & 0x3f) << 8) | u16::from(low);
+                let reason = if target as usize >= full_dns_message.len() {
+                    Some(NameError::PointerOutOfRange(target))
+                } else if target as usize >= pos {
+                    Some(NameError::ForwardPointer(target))
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Err(DnsParseError::invalid_name(pos, reason));
+                }
+                if visited.is_empty() {
+                    visited.resize(full_dns_message.len() / 64 + 1, 0);
+                }
+                let (word, bit) = (target as usize / 64, 1u64 << (target % 64));
+                if visited[word] & bit != 0 {
+                    error!("pointer loop at offset {}", pos);
+                    return Err(DnsParseError::invalid_name(
+                        pos,
+                        NameError::PointerLoop(target),
+                    ));
+                }
+                visited[word] |= bit;
+                pos = target as usize;
+            }
+            _ => {
+                return Err(DnsParseError::invalid_name(
+                    pos,
+                    NameError::LabelTooLong(len),
+                ))
             }
-            Some(_name) => &self.name,
         }
     }
 }
 
+/// A name as found in place: labels ending with the root or a pointer.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub(crate) struct CompressedNameChain<'a> {
+    raw: &'a [u8],
+}
+
+impl<'a> CompressedNameChain<'a> {
+    /// Decompress the name against the message it was parsed from.
+    ///
+    /// A chain that is not part of `full_dns_message` is decoded on its own.
+    pub fn resolve_name(&self, full_dns_message: &[u8]) -> Result<DomainName, DnsParseError> {
+        let labels = match offset_in(full_dns_message, self.raw) {
+            Some(offset) => decompress_name(full_dns_message, offset)?,
+            None => decompress_name(self.raw, 0)?,
+        };
+        Ok(DomainName::from_decoded(&labels))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
-pub enum CompressedName<'a> {
-    Label(&'a str),
+pub(crate) enum CompressedName<'a> {
+    Label(&'a [u8]),
     Pointer(u16),
 }
 
 #[rustfmt::skip]
-fn parse_compressed_token(i: &[u8]) -> IResult<&[u8], Option<CompressedName>> {
+fn parse_compressed_token(i: &[u8]) -> IResult<&[u8], Option<CompressedName<'_>>> {
     let (i, len) = be_u8(i)?;
     let tt = (len & 0xc0) >> 6;
-    let len = len & 0x3f;
     match tt {
         0b00 => match len {
             0 => Ok((i, None)),
             _ => do_parse!(i,
-                    name: map_res!(take!(len), std::str::from_utf8) >>
+                    name: take!(len) >>
                     (Some(CompressedName::Label(name)))
                  ),
         },
         0b11 => {
             let (i, len2) = be_u8(i)?;
-            let offs = u16::from(len) << 8;
+            let offs = u16::from(len & 0x3f) << 8;
             let offs = offs | u16::from(len2);
             value!(i, Some(CompressedName::Pointer(offs)))
         }
         _ => {
             error!("invalid type byte found! 0b{:b}", tt);
-            value!(i, None)
+            Err(Err::Error(error_position!(i, ErrorKind::Custom(u32::from(len)))))
         }
     }
 }
 
-pub(crate) fn parse_compressed_chain(i: &[u8]) -> IResult<&[u8], CompressedNameChain> {
-    let mut names = CompressedNameChain::default();
-    if i[0] == 0u8 {
-        let (i, _) = take!(i, 1).unwrap();
-        names.name = Some(String::from("<ROOT>"));
-        value!(i, names)
-    } else {
-        let mut rest = i;
-        for _ in 0..100 {
-            let (i, name) = parse_compressed_token(rest)?;
-            rest = i;
-            match name {
-                Some(value) => {
-                    names.push(value);
-                    if let CompressedName::Pointer(_) = value {
-                        break;
-                    }
-                }
-                None => break,
-            }
+pub(crate) fn parse_compressed_chain(i: &[u8]) -> IResult<&[u8], CompressedNameChain<'_>> {
+    let mut rest = i;
+    loop {
+        let (r, name) = parse_compressed_token(rest)?;
+        rest = r;
+        match name {
+            Some(CompressedName::Label(_)) => {}
+            Some(CompressedName::Pointer(_)) | None => break,
         }
-        value!(rest, names)
     }
+    let raw = &i[..i.len() - rest.len()];
+    value!(rest, CompressedNameChain { raw })
 }
 
 #[cfg(test)]
@@ -112,6 +178,11 @@ mod tests {
     use super::*;
 
     const DNS_RESPONSE: &[u8] = include_bytes!("../../../fixtures/dns/dns_response1.bin");
+    const DNS_LOOPING_POINTER: &[u8] =
+        include_bytes!("../../../fixtures/dns/dns_looping_pointer.bin");
+    const DNS_FORWARD_POINTER: &[u8] =
+        include_bytes!("../../../fixtures/dns/dns_forward_pointer.bin");
+    const DNS_LONG_NAME: &[u8] = include_bytes!("../../../fixtures/dns/dns_long_name.bin");
 
     #[test]
     fn test_parse_compressed_token() {
@@ -119,21 +190,19 @@ mod tests {
             0x06, 0x67, 0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
         ];
         let (_rest, name) = parse_compressed_token(&data).unwrap();
-        assert_eq!(Some(CompressedName::Label("google")), name)
+        assert_eq!(Some(CompressedName::Label(b"google")), name)
     }
 
     #[test]
     fn test_compressed_name_chain() {
-        let mut cn = CompressedNameChain::default();
-        let l1 = String::from("host");
-        let l2 = String::from("subdomain");
-        cn.push(CompressedName::Label(&l1));
-        cn.push(CompressedName::Label(&l2));
-        cn.push(CompressedName::Pointer(12));
-
-        let name = cn.resolve_name(0, DNS_RESPONSE);
-        let should_be = &Some("host.subdomain.google.com".to_string());
-        assert_eq!(should_be, name);
+        let mut data = DNS_RESPONSE.to_vec();
+        let offset = data.len();
+        data.extend_from_slice(b"\x04host\x09subdomain\xc0\x0c");
+
+        let (rest, cn) = parse_compressed_chain(&data[offset..]).unwrap();
+        assert_eq!(0, rest.len());
+        let name = cn.resolve_name(&data).unwrap();
+        assert_eq!("host.subdomain.google.com", name.to_string());
     }
 
     #[test]
@@ -142,12 +211,94 @@ mod tests {
             0x06, 0x67, 0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
         ];
 
-        let (rest, mut nc) = parse_compressed_chain(&data).unwrap();
+        let (rest, nc) = parse_compressed_chain(&data).unwrap();
         println!("{:?}", nc);
-        let name = nc.resolve_name(0, DNS_RESPONSE);
-        let should_be: &Option<String> = &Some("google.com".to_string());
-        assert_eq!(should_be, name);
+        let name = nc.resolve_name(DNS_RESPONSE).unwrap();
+        assert_eq!("google.com", name.to_string());
         assert_eq!(0, rest.len(), "should eat nullbyte at end");
     }
 
+    #[test]
+    fn test_binary_labels() {
+        let data = b"\x03a.b\x04\xff\x00 \\\x05t\"q;x\x03com\x00";
+        let (_, nc) = parse_compressed_chain(data).unwrap();
+        let name = nc.resolve_name(data).unwrap();
+        assert_eq!(r#"a\.b.\255\000\032\\.t\"q\;x.com"#, name.to_string());
+        let labels: Vec<_> = name.labels().collect();
+        assert_eq!(&b"a.b"[..], labels[0]);
+        assert_eq!(&[0xff, 0x00, b' ', b'\\'][..], labels[1]);
+    }
+
+    #[test]
+    fn test_parse_compressed_chain_truncated() {
+        assert!(parse_compressed_chain(&[]).is_err());
+        assert!(parse_compressed_chain(&[0x06, 0x67, 0x6f]).is_err());
+        assert!(parse_compressed_chain(&[0x80, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_decompress_name() {
+        let labels = decompress_name(DNS_RESPONSE, 0x2a).unwrap();
+        assert_eq!(vec![&b"smtp4"[..], b"google", b"com"], labels);
+        assert!(decompress_name(&[0], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_looping_pointer() {
+        let err = decompress_name(DNS_LOOPING_POINTER, 12).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(0x10, NameError::ForwardPointer(0x16)),
+            err
+        );
+
+        // Backward pointers only, but the second one lands on the first.
+        let data = b"\x01a\xc0\x00";
+        let err = decompress_name(data, 2).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(2, NameError::PointerLoop(0)),
+            err
+        );
+    }
+
+    #[test]
+    fn test_forward_pointer() {
+        let err = decompress_name(DNS_FORWARD_POINTER, 12).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(16, NameError::ForwardPointer(0x20)),
+            err
+        );
+
+        let err = decompress_name(b"\x01a\xc0\x02", 0).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(2, NameError::ForwardPointer(2)),
+            err
+        );
+    }
+
+    #[test]
+    fn test_pointer_out_of_range() {
+        let err = decompress_name(b"\x01a\xff\xff", 0).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(2, NameError::PointerOutOfRange(0x3fff)),
+            err
+        );
+    }
+
+    #[test]
+    fn test_name_limits() {
+        let err = decompress_name(DNS_LONG_NAME, 12).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(12 + 3 * 64, NameError::NameTooLong),
+            err
+        );
+
+        let err = decompress_name(b"\x01a\x40", 0).unwrap_err();
+        assert_eq!(
+            DnsParseError::invalid_name(2, NameError::LabelTooLong(0x40)),
+            err
+        );
+
+        let err = decompress_name(b"\x01a\x05abc", 0).unwrap_err();
+        assert_eq!(DnsParseError::invalid_name(2, NameError::Truncated), err);
+    }
 }
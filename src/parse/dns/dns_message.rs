@@ -1,7 +1,7 @@
 use crate::parse::dns::*;
 use nom::*;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DnsHeader {
     pub id: u16,
     pub flags: Flags,
@@ -12,7 +12,7 @@ pub struct DnsHeader {
 }
 
 #[rustfmt::skip]
-named!(parse_dns_header<&[u8], DnsHeader>, do_parse!(
+named!(pub (crate) parse_dns_header<&[u8], DnsHeader>, do_parse!(
     id: be_u16 >>
     flags: parse_flags >>
     qdcount: be_u16 >>
@@ -24,48 +24,197 @@ named!(parse_dns_header<&[u8], DnsHeader>, do_parse!(
     )
 ));
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DnsMessage<'a> {
     pub header: DnsHeader,
-    pub queries: Vec<Query<'a>>,
+    pub queries: Vec<Query>,
     pub answers: Vec<ResourceRecord<'a>>,
     pub nsrecords: Vec<ResourceRecord<'a>>,
     pub arecords: Vec<ResourceRecord<'a>>,
 }
 
-#[rustfmt::skip]
-named!(parse_dns_message<&[u8], DnsMessage>, do_parse!(
-    header: parse_dns_header >>
-    queries: count!(parse_query, header.qdcount as usize) >>
-    answers: count!(parse_resource_record, header.ancount as usize) >>
-    nsrecords: count!(parse_resource_record, header.nscount as usize) >>
-    arecords: count!(parse_resource_record, header.arcount as usize) >>
-    (
-        DnsMessage{header, queries, answers, nsrecords, arecords}
-    )
-));
+/// A `DnsMessage` that owns all its data, so it can be stored or sent to
+/// another thread after the packet buffer is gone.
+pub type OwnedDnsMessage = DnsMessage<'static>;
 
-pub fn dns_message(data: &[u8], resolve_resource_records: bool) -> IResult<&[u8], DnsMessage> {
-    let (rest, mut dns_message) = parse_dns_message(data).unwrap();
+impl<'a> DnsMessage<'a> {
+    /// Copy all borrowed rdata, decoded records are kept as they are.
+    pub fn into_owned(self) -> OwnedDnsMessage {
+        let owned = |records: Vec<ResourceRecord<'a>>| -> Vec<ResourceRecord<'static>> {
+            records
+                .into_iter()
+                .map(ResourceRecord::into_owned)
+                .collect()
+        };
+        DnsMessage {
+            header: self.header,
+            queries: self.queries,
+            answers: owned(self.answers),
+            nsrecords: owned(self.nsrecords),
+            arecords: owned(self.arecords),
+        }
+    }
+}
 
-    for q in dns_message.queries.iter_mut() {
-        q.name_chain.resolve_name(0, data);
+impl DnsMessage<'_> {
+    /// The OPT pseudo-record, if the message has one.
+    pub fn opt(&self) -> Option<&ResourceRecord<'_>> {
+        self.arecords.iter().find(|rr| rr.rrtype == DnsType::OPT)
     }
 
-    if resolve_resource_records {
-        for rr in dns_message.answers.iter_mut() {
-            rr.resolve(data);
+    /// Extended DNS Errors from the OPT record, if it has been resolved.
+    pub fn extended_errors(&self) -> Vec<&OptionExtendedError> {
+        match self.opt().and_then(|rr| rr.record.as_ref()) {
+            Some(RRecordTypes::OPT(opt)) => opt
+                .options
+                .iter()
+                .filter_map(|option| match option {
+                    EdnsOption::EDE(ede) => Some(ede),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
         }
+    }
+
+    /// Add the upper rcode bits from the OPT record to the header rcode.
+    pub(crate) fn apply_extended_rcode(&mut self) {
+        let upper = match self.opt() {
+            Some(opt) => (opt.ttl >> 24) as u16,
+            None => return,
+        };
+        let rcode = upper << 4 | u16::from(&self.header.flags.rcode) & 0xf;
+        self.header.flags.rcode = Rcode::from(rcode);
+    }
+}
 
-        for rr in dns_message.nsrecords.iter_mut() {
-            rr.resolve(data);
+/// Parse `count` items from `i` with `parser`, tagging errors with `section`.
+///
+/// With `partial`, an item cut short by the end of the message ends the
+/// section and sets `truncated`, which makes later sections empty.
+fn parse_section<'a, T>(
+    data: &'a [u8],
+    i: &'a [u8],
+    section: Section,
+    count: u16,
+    parser: fn(&'a [u8], &'a [u8]) -> ParseResult<'a, T>,
+    partial: bool,
+    truncated: &mut bool,
+) -> ParseResult<'a, Vec<T>> {
+    let mut items = Vec::new();
+    let mut rest = i;
+    for index in 0..count as usize {
+        if *truncated {
+            break;
         }
+        match parser(data, rest) {
+            Ok((r, item)) => {
+                items.push(item);
+                rest = r;
+            }
+            Err(DnsParseError::Truncated { .. }) if partial => *truncated = true,
+            Err(e) => return Err(e.at(section, index)),
+        }
+    }
+    Ok((rest, items))
+}
 
-        for rr in dns_message.arecords.iter_mut() {
-            rr.resolve(data);
+fn parse_dns_message(data: &[u8], partial: bool) -> ParseResult<'_, DnsMessage<'_>> {
+    let (rest, header) =
+        parse_dns_header(data).map_err(|_| DnsParseError::truncated(data.len()))?;
+    let truncated = &mut false;
+    let (rest, queries) = parse_section(
+        data,
+        rest,
+        Section::Question,
+        header.qdcount,
+        parse_query,
+        partial,
+        truncated,
+    )?;
+    let (rest, answers) = parse_section(
+        data,
+        rest,
+        Section::Answer,
+        header.ancount,
+        parse_resource_record,
+        partial,
+        truncated,
+    )?;
+    let (rest, nsrecords) = parse_section(
+        data,
+        rest,
+        Section::Authority,
+        header.nscount,
+        parse_resource_record,
+        partial,
+        truncated,
+    )?;
+    let (rest, arecords) = parse_section(
+        data,
+        rest,
+        Section::Additional,
+        header.arcount,
+        parse_resource_record,
+        partial,
+        truncated,
+    )?;
+    Ok((
+        rest,
+        DnsMessage {
+            header,
+            queries,
+            answers,
+            nsrecords,
+            arecords,
+        },
+    ))
+}
+
+/// Parse a dns message.
+///
+/// With `resolve_resource_records` the rdata of every resource record is
+/// decoded as well. Any input is accepted; malformed messages give an error.
+/// The header rcode includes the extended bits from an OPT record.
+pub fn dns_message(
+    data: &[u8],
+    resolve_resource_records: bool,
+) -> Result<(&[u8], DnsMessage<'_>), DnsParseError> {
+    decode_message(data, resolve_resource_records, false)
+}
+
+/// Like `dns_message`, but a message that ends early keeps the records
+/// before the cut. The header counts are left as they were, so
+/// `DnsMessage::validate` reports the missing records, and the bytes of
+/// a record cut in half are returned as the rest.
+pub fn dns_message_partial(
+    data: &[u8],
+    resolve_resource_records: bool,
+) -> Result<(&[u8], DnsMessage<'_>), DnsParseError> {
+    decode_message(data, resolve_resource_records, true)
+}
+
+fn decode_message(
+    data: &[u8],
+    resolve_resource_records: bool,
+    partial: bool,
+) -> Result<(&[u8], DnsMessage<'_>), DnsParseError> {
+    let (rest, mut dns_message) = parse_dns_message(data, partial)?;
+    dns_message.apply_extended_rcode();
+
+    if resolve_resource_records {
+        let sections = [
+            (Section::Answer, &mut dns_message.answers),
+            (Section::Authority, &mut dns_message.nsrecords),
+            (Section::Additional, &mut dns_message.arecords),
+        ];
+        for (section, records) in sections {
+            for (i, rr) in records.iter_mut().enumerate() {
+                rr.resolve_at(data, section, i);
+            }
         }
     }
-    value!(rest, dns_message)
+    Ok((rest, dns_message))
 }
 
 #[cfg(test)]
@@ -76,7 +225,7 @@ mod tests {
 
     #[test]
     fn test_parse_dns_message() {
-        let (_res, mut dns_message) = parse_dns_message(DNS_RESPONSE).unwrap();
+        let (_res, mut dns_message) = parse_dns_message(DNS_RESPONSE, false).unwrap();
         println!("{:?}", dns_message);
         assert_eq!(dns_message.header.id, 63343);
         assert_eq!(dns_message.header.qdcount, 1);
@@ -86,8 +235,7 @@ mod tests {
 
         println!("--");
         println!("Queries:");
-        for (i, anrr) in dns_message.queries.iter_mut().enumerate() {
-            anrr.name_chain.resolve_name(0, DNS_RESPONSE);
+        for (i, anrr) in dns_message.queries.iter().enumerate() {
             println!("  {}: {:?}", i, anrr);
         }
 
@@ -109,16 +257,142 @@ mod tests {
         let (_rest, dns_message) = dns_message(DNS_RESPONSE, true).unwrap();
         println!("{:#x?}", dns_message);
         assert_eq!(dns_message.header.arcount, 6);
-        let should_be = Some("smtp3.google.com".to_string());
-        assert_eq!(should_be, dns_message.arecords[5].name_chain.name);
+        let should_be: DomainName = "smtp3.google.com".parse().unwrap();
+        assert_eq!(should_be, dns_message.arecords[5].name);
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let (_rest, borrowed) = dns_message(DNS_RESPONSE, true).unwrap();
+        let owned: OwnedDnsMessage = {
+            let data = DNS_RESPONSE.to_vec();
+            let (_rest, dns_message) = dns_message(&data, true).unwrap();
+            dns_message.into_owned()
+        };
+        assert_eq!(borrowed, owned);
+
+        let handle = std::thread::spawn(move || owned.arecords[5].record.clone());
+        let record = handle.join().unwrap();
+        assert_eq!(borrowed.arecords[5].record, record);
     }
 
     #[test]
     fn test_should_handle_looping_pointers() {
         #[allow(unused_variables)]
         let dns_data: &[u8] = include_bytes!("../../../fixtures/dns/dns_looping_pointer.bin");
-        let (_rest, dns_message) = dns_message(dns_data, false).unwrap();
-        println!("{:?}", dns_message);
+        let err = dns_message(dns_data, false).unwrap_err();
+        println!("{}", err);
+        assert_eq!(Section::Question, err.section());
+        assert_eq!(0, err.index());
+        assert!(matches!(
+            err,
+            DnsParseError::InvalidName {
+                reason: NameError::ForwardPointer(0x16),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_non_utf8_qname() {
+        let data = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x03\xe4\xb8.\x02io\x00\x00\x01\x00\x01";
+        let (_rest, dns_message) = dns_message(data, true).unwrap();
+        let name = dns_message.queries[0].name.to_string();
+        assert_eq!("\\228\\184\\..io", name);
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        let err = dns_message(&DNS_RESPONSE[..8], true).unwrap_err();
+        assert_eq!(DnsParseError::truncated(8), err);
+
+        let err = dns_message(&DNS_RESPONSE[..40], true).unwrap_err();
+        assert_eq!(Section::Answer, err.section());
+        assert_eq!(0, err.index());
+        assert_eq!(28, err.offset());
     }
 
+    #[test]
+    fn test_extended_rcode() {
+        let flags = Flags {
+            qr: Qr::Response,
+            rcode: Rcode::BadCookie,
+            ..Default::default()
+        };
+        let opt = OptRecord {
+            udp_payload_size: 1232,
+            e_rcode: 1,
+            version: 0,
+            opt_do: 0,
+            z: 0,
+            options: vec![EdnsOption::new(15, b"\x00\x06")],
+        };
+        let msg = DnsMessageBuilder::new(1)
+            .flags(flags)
+            .additional(ResourceRecord::new(
+                DomainName::root(),
+                DnsClass::from(1232),
+                0,
+                RRecordTypes::OPT(opt),
+            ))
+            .build();
+        let wire = msg.encode(true);
+        assert_eq!(7, wire[3] & 0xf);
+        // The upper rcode bits are written to the OPT TTL.
+        assert_eq!(&[1, 0, 0, 0], &wire[wire.len() - 12..wire.len() - 8]);
+        let (_rest, parsed) = dns_message(&wire, true).unwrap();
+        assert_eq!(Rcode::BadCookie, parsed.header.flags.rcode);
+        let errors = parsed.extended_errors();
+        assert_eq!(1, errors.len());
+        assert_eq!(Some("DNSSEC Bogus"), errors[0].purpose);
+
+        let mut without_opt = wire[..12].to_vec();
+        without_opt[11] = 0;
+        let (_rest, parsed) = dns_message(&without_opt, true).unwrap();
+        assert_eq!(Rcode::XrrSet, parsed.header.flags.rcode);
+    }
+
+    #[test]
+    fn test_no_input_panics() {
+        for len in 0..DNS_RESPONSE.len() {
+            let _ = dns_message(&DNS_RESPONSE[..len], true);
+            let _ = dns_message_partial(&DNS_RESPONSE[..len], true);
+        }
+        let mut data = DNS_RESPONSE.to_vec();
+        for pos in 0..data.len() {
+            let orig = data[pos];
+            for byte in [0x00, 0x01, 0x3f, 0x40, 0x80, 0xc0, 0xff] {
+                data[pos] = byte;
+                let _ = dns_message(&data, true);
+            }
+            data[pos] = orig;
+        }
+    }
+
+    #[test]
+    fn test_partial_message() {
+        // Cut the response in the middle of the fourth answer.
+        let data = &DNS_RESPONSE[..100];
+        assert!(matches!(
+            dns_message(data, true),
+            Err(DnsParseError::Truncated {
+                section: Section::Answer,
+                index: 3,
+                ..
+            })
+        ));
+        let (rest, msg) = dns_message_partial(data, true).unwrap();
+        assert_eq!(3, msg.answers.len());
+        assert!(msg.arecords.is_empty());
+        let violations = msg.validate(rest);
+        assert_eq!(
+            Violation::CountMismatch {
+                section: Section::Answer,
+                count: 6,
+                records: 3
+            },
+            violations[0]
+        );
+        assert_eq!(Violation::TrailingBytes(rest.len()), violations[2]);
+    }
 }
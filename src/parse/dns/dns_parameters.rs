@@ -1,5 +1,5 @@
 use nom::*;
-use serde_derive::Serialize;
+use serde::{Serialize, Serializer};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[rustfmt::skip]
@@ -10,18 +10,81 @@ pub enum Qr {
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[rustfmt::skip]
 pub enum Opcode {
-    Query, IQuery, Status, Reserved,
+    Query, IQuery, Status, Notify, Update, Dso,
+    Other(u8),
 }
 
+/// Response code. Values above 15 need the upper bits from the OPT
+/// record (RFC 6891 6.1.3). `BadSig` shares 16 with `BadVers` and is only
+/// used in TSIG records.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[rustfmt::skip]
 pub enum Rcode {
     NoError, FormErr, ServFail, NXDomain,
     NotImp, Refused, YXDomain, XrrSet,
-    NotAuth, NotZone, Other(u8),
+    NXRRSet, NotAuth, NotZone, DsoTypeNI,
+    BadVers, BadSig, BadKey, BadTime,
+    BadMode, BadName, BadAlg, BadTrunc,
+    BadCookie, Other(u16),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl From<u16> for Rcode {
+    fn from(rcode: u16) -> Rcode {
+        match rcode & 0xfff {
+            0 => Rcode::NoError,
+            1 => Rcode::FormErr,
+            2 => Rcode::ServFail,
+            3 => Rcode::NXDomain,
+            4 => Rcode::NotImp,
+            5 => Rcode::Refused,
+            6 => Rcode::YXDomain,
+            7 => Rcode::XrrSet,
+            8 => Rcode::NXRRSet,
+            9 => Rcode::NotAuth,
+            10 => Rcode::NotZone,
+            11 => Rcode::DsoTypeNI,
+            16 => Rcode::BadVers,
+            17 => Rcode::BadKey,
+            18 => Rcode::BadTime,
+            19 => Rcode::BadMode,
+            20 => Rcode::BadName,
+            21 => Rcode::BadAlg,
+            22 => Rcode::BadTrunc,
+            23 => Rcode::BadCookie,
+            rcode => Rcode::Other(rcode),
+        }
+    }
+}
+
+impl From<&Rcode> for u16 {
+    fn from(rcode: &Rcode) -> u16 {
+        match rcode {
+            Rcode::NoError => 0,
+            Rcode::FormErr => 1,
+            Rcode::ServFail => 2,
+            Rcode::NXDomain => 3,
+            Rcode::NotImp => 4,
+            Rcode::Refused => 5,
+            Rcode::YXDomain => 6,
+            Rcode::XrrSet => 7,
+            Rcode::NXRRSet => 8,
+            Rcode::NotAuth => 9,
+            Rcode::NotZone => 10,
+            Rcode::DsoTypeNI => 11,
+            Rcode::BadVers | Rcode::BadSig => 16,
+            Rcode::BadKey => 17,
+            Rcode::BadTime => 18,
+            Rcode::BadMode => 19,
+            Rcode::BadName => 20,
+            Rcode::BadAlg => 21,
+            Rcode::BadTrunc => 22,
+            Rcode::BadCookie => 23,
+            Rcode::Other(rcode) => rcode & 0xfff,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Flags {
     pub qr: Qr,
     pub opcode: Opcode,
@@ -29,6 +92,9 @@ pub struct Flags {
     pub tc: bool,
     pub rd: bool,
     pub ra: bool,
+    pub z: bool,
+    pub ad: bool,
+    pub cd: bool,
     pub rcode: Rcode,
 }
 
@@ -45,93 +111,391 @@ pub(crate) fn parse_flags(i: &[u8]) -> IResult<&[u8], Flags> {
         0 => Opcode::Query,
         1 => Opcode::IQuery,
         2 => Opcode::Status,
-        _ => Opcode::Reserved,
+        4 => Opcode::Notify,
+        5 => Opcode::Update,
+        6 => Opcode::Dso,
+        opcode => Opcode::Other(opcode as u8),
     };
 
     let aa = (flags & 0x400) == 0x400;
     let tc = (flags & 0x200) == 0x200;
     let rd = (flags & 0x100) == 0x100;
     let ra = (flags & 0x80) == 0x80;
+    let z = (flags & 0x40) == 0x40;
+    let ad = (flags & 0x20) == 0x20;
+    let cd = (flags & 0x10) == 0x10;
 
-    let rcode = match flags & 0xf {
-        0 => Rcode::NoError,
-        1 => Rcode::FormErr,
-        2 => Rcode::ServFail,
-        3 => Rcode::NXDomain,
-        4 => Rcode::NotImp,
-        5 => Rcode::Refused,
-        6 => Rcode::YXDomain,
-        7 => Rcode::XrrSet,
-        8 => Rcode::NotAuth,
-        9 => Rcode::NotZone,
-        _ => Rcode::Other((flags & 0xf) as u8),
-    };
+    let rcode = Rcode::from(flags & 0xf);
 
     #[rustfmt::skip]
-    let flags = Flags { qr, opcode, aa, tc, rd, ra, rcode };
+    let flags = Flags { qr, opcode, aa, tc, rd, ra, z, ad, cd, rcode };
 
     value!(i, flags)
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-#[rustfmt::skip]
-pub enum DnsType {
-    A, NS, CNAME, SOA, WKS, PTR, HINFO, MX, TXT, AXFR, ALL,
-    AAAA, LOC, SPF, SRV, TKEY, TSIG, IXFR, URI, TA, DLV,
-    OPT, NSEC,
-    UnknownType(u16),
+impl Default for Flags {
+    /// A standard query with all bits cleared.
+    fn default() -> Flags {
+        Flags {
+            qr: Qr::Query,
+            opcode: Opcode::Query,
+            aa: false,
+            tc: false,
+            rd: false,
+            ra: false,
+            z: false,
+            ad: false,
+            cd: false,
+            rcode: Rcode::NoError,
+        }
+    }
 }
 
-#[rustfmt::skip]
-named!(pub (crate) parse_dnstype<&[u8], DnsType>, do_parse!(
-    qtype: be_u16 >>
-    (match qtype {
-            1   => DnsType::A,
-            2   => DnsType::NS,
-            5   => DnsType::CNAME,
-            6   => DnsType::SOA,
-            11  => DnsType::WKS,
-            12  => DnsType::PTR,
-            13  => DnsType::HINFO,
-            15  => DnsType::MX,
-            16  => DnsType::TXT,
-            28  => DnsType::AAAA,
-            29  => DnsType::LOC,
-            33  => DnsType::SRV,
-            41  => DnsType::OPT,
-            47  => DnsType::NSEC,
-            99  => DnsType::SPF,
-            249 => DnsType::TKEY,
-            250 => DnsType::TSIG,
-            251 => DnsType::IXFR,
-            252 => DnsType::AXFR,
-            255 => DnsType::ALL,
-            256 => DnsType::URI,
-            32768 => DnsType::TA,
-            32769 => DnsType::DLV,
-            _   => DnsType::UnknownType(qtype),
-    })
-));
-
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+impl Flags {
+    /// Names of the bits that are set, in header order.
+    pub fn names(&self) -> Vec<&'static str> {
+        let bits = [
+            (self.qr == Qr::Response, "qr"),
+            (self.aa, "aa"),
+            (self.tc, "tc"),
+            (self.rd, "rd"),
+            (self.ra, "ra"),
+            (self.z, "z"),
+            (self.ad, "ad"),
+            (self.cd, "cd"),
+        ];
+        bits.iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl From<&Flags> for u16 {
+    fn from(flags: &Flags) -> u16 {
+        let qr = match flags.qr {
+            Qr::Query => 0,
+            Qr::Response => 1,
+        };
+        let opcode = match flags.opcode {
+            Opcode::Query => 0,
+            Opcode::IQuery => 1,
+            Opcode::Status => 2,
+            Opcode::Notify => 4,
+            Opcode::Update => 5,
+            Opcode::Dso => 6,
+            Opcode::Other(opcode) => u16::from(opcode & 0xf),
+        };
+        let rcode = u16::from(&flags.rcode) & 0xf;
+        (qr << 15)
+            | (opcode << 11)
+            | (u16::from(flags.aa) << 10)
+            | (u16::from(flags.tc) << 9)
+            | (u16::from(flags.rd) << 8)
+            | (u16::from(flags.ra) << 7)
+            | (u16::from(flags.z) << 6)
+            | (u16::from(flags.ad) << 5)
+            | (u16::from(flags.cd) << 4)
+            | rcode
+    }
+}
+
+/// Error from parsing an unknown type or class mnemonic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownMnemonic(pub String);
+
+impl std::fmt::Display for UnknownMnemonic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "unknown mnemonic {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownMnemonic {}
+
+/// Define `DnsType` from the IANA table, with numeric conversions and
+/// mnemonics. The mnemonic is the variant name unless given.
+macro_rules! dns_types {
+    ($($variant:ident = $value:literal $(as $mnemonic:literal)?,)*) => {
+        /// Resource record type (IANA "Resource Record (RR) TYPEs").
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum DnsType {
+            $($variant,)*
+            UnknownType(u16),
+        }
+
+        impl From<u16> for DnsType {
+            fn from(qtype: u16) -> DnsType {
+                match qtype {
+                    $($value => DnsType::$variant,)*
+                    _ => DnsType::UnknownType(qtype),
+                }
+            }
+        }
+
+        impl From<&DnsType> for u16 {
+            fn from(dnstype: &DnsType) -> u16 {
+                match dnstype {
+                    $(DnsType::$variant => $value,)*
+                    DnsType::UnknownType(qtype) => *qtype,
+                }
+            }
+        }
+
+        impl DnsType {
+            /// The registered mnemonic, `None` for unassigned types.
+            pub fn mnemonic(&self) -> Option<&'static str> {
+                match self {
+                    $(DnsType::$variant => Some(dns_types!(@name $variant $($mnemonic)?)),)*
+                    DnsType::UnknownType(_) => None,
+                }
+            }
+
+            fn from_mnemonic(s: &str) -> Option<DnsType> {
+                match s {
+                    $(dns_types!(@name $variant $($mnemonic)?) => Some(DnsType::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+    (@name $variant:ident $mnemonic:literal) => { $mnemonic };
+    (@name $variant:ident) => { stringify!($variant) };
+}
+
+dns_types! {
+    A = 1,
+    NS = 2,
+    MD = 3,
+    MF = 4,
+    CNAME = 5,
+    SOA = 6,
+    MB = 7,
+    MG = 8,
+    MR = 9,
+    NULL = 10,
+    WKS = 11,
+    PTR = 12,
+    HINFO = 13,
+    MINFO = 14,
+    MX = 15,
+    TXT = 16,
+    RP = 17,
+    AFSDB = 18,
+    X25 = 19,
+    ISDN = 20,
+    RT = 21,
+    NSAP = 22,
+    NSAPPTR = 23 as "NSAP-PTR",
+    SIG = 24,
+    KEY = 25,
+    PX = 26,
+    GPOS = 27,
+    AAAA = 28,
+    LOC = 29,
+    NXT = 30,
+    EID = 31,
+    NIMLOC = 32,
+    SRV = 33,
+    ATMA = 34,
+    NAPTR = 35,
+    KX = 36,
+    CERT = 37,
+    A6 = 38,
+    DNAME = 39,
+    SINK = 40,
+    OPT = 41,
+    APL = 42,
+    DS = 43,
+    SSHFP = 44,
+    IPSECKEY = 45,
+    RRSIG = 46,
+    NSEC = 47,
+    DNSKEY = 48,
+    DHCID = 49,
+    NSEC3 = 50,
+    NSEC3PARAM = 51,
+    TLSA = 52,
+    SMIMEA = 53,
+    HIP = 55,
+    NINFO = 56,
+    RKEY = 57,
+    TALINK = 58,
+    CDS = 59,
+    CDNSKEY = 60,
+    OPENPGPKEY = 61,
+    CSYNC = 62,
+    ZONEMD = 63,
+    SVCB = 64,
+    HTTPS = 65,
+    DSYNC = 66,
+    HHIT = 67,
+    BRID = 68,
+    SPF = 99,
+    UINFO = 100,
+    UID = 101,
+    GID = 102,
+    UNSPEC = 103,
+    NID = 104,
+    L32 = 105,
+    L64 = 106,
+    LP = 107,
+    EUI48 = 108,
+    EUI64 = 109,
+    NXNAME = 128,
+    TKEY = 249,
+    TSIG = 250,
+    IXFR = 251,
+    AXFR = 252,
+    MAILB = 253,
+    MAILA = 254,
+    ALL = 255 as "ANY",
+    URI = 256,
+    CAA = 257,
+    AVC = 258,
+    DOA = 259,
+    AMTRELAY = 260,
+    RESINFO = 261,
+    WALLET = 262,
+    CLA = 263,
+    IPN = 264,
+    TA = 32768,
+    DLV = 32769,
+}
+
+named!(pub (crate) parse_dnstype<&[u8], DnsType>, map!(be_u16, DnsType::from));
+
+/// The mnemonic, or `TYPEnnn` (RFC 3597) for unassigned types.
+impl std::fmt::Display for DnsType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.mnemonic() {
+            Some(mnemonic) => write!(f, "{}", mnemonic),
+            None => write!(f, "TYPE{}", u16::from(self)),
+        }
+    }
+}
+
+/// Accepts mnemonics in any case, `TYPEnnn`, and `*` or `ALL` for `ANY`.
+impl std::str::FromStr for DnsType {
+    type Err = UnknownMnemonic;
+
+    fn from_str(s: &str) -> Result<DnsType, UnknownMnemonic> {
+        let upper = s.to_ascii_uppercase();
+        if let Some(Ok(qtype)) = upper.strip_prefix("TYPE").map(str::parse::<u16>) {
+            return Ok(DnsType::from(qtype));
+        }
+        match upper.as_str() {
+            "*" | "ALL" => Ok(DnsType::ALL),
+            _ => DnsType::from_mnemonic(&upper).ok_or_else(|| UnknownMnemonic(s.to_string())),
+        }
+    }
+}
+
+impl Serialize for DnsType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Resource record class (IANA "DNS CLASSes").
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[rustfmt::skip]
 pub enum DnsClass {
-    IN, CS, CH, HS, ALL,
+    IN, CS, CH, HS, NONE, ALL,
     UnknownClass(u16), OtherUsage(u16),
 }
 
-#[rustfmt::skip]
-named!(pub (crate) parse_dnsclass<&[u8], DnsClass>, do_parse!(
-    qclass: be_u16 >>
-    (match qclass & 32767 { // Clear the "UNICAST-RESPONSE" flag.
+named!(pub (crate) parse_dnsclass<&[u8], DnsClass>, map!(be_u16, DnsClass::from));
+
+impl From<u16> for DnsClass {
+    #[rustfmt::skip]
+    fn from(qclass: u16) -> DnsClass {
+        match qclass {
             1   => DnsClass::IN,
             2   => DnsClass::CS,
             3   => DnsClass::CH,
             4   => DnsClass::HS,
+            254 => DnsClass::NONE,
             255 => DnsClass::ALL,
             _   => DnsClass::UnknownClass(qclass),
-    })
-));
+        }
+    }
+}
+
+impl From<&DnsClass> for u16 {
+    #[rustfmt::skip]
+    fn from(dnsclass: &DnsClass) -> u16 {
+        match dnsclass {
+            DnsClass::IN   => 1,
+            DnsClass::CS   => 2,
+            DnsClass::CH   => 3,
+            DnsClass::HS   => 4,
+            DnsClass::NONE => 254,
+            DnsClass::ALL  => 255,
+            DnsClass::UnknownClass(qclass) | DnsClass::OtherUsage(qclass) => *qclass,
+        }
+    }
+}
+
+impl DnsClass {
+    /// Split off the top bit, which mDNS uses as the unicast-response
+    /// flag in questions and the cache-flush flag in records (RFC 6762
+    /// 5.4, 10.2). Classes that are unknown without the bit are kept.
+    pub fn split_mdns_bit(self) -> (DnsClass, bool) {
+        match self {
+            DnsClass::UnknownClass(qclass) if qclass & 0x8000 != 0 => {
+                match DnsClass::from(qclass & 0x7fff) {
+                    DnsClass::UnknownClass(_) => (self, false),
+                    known => (known, true),
+                }
+            }
+            _ => (self, false),
+        }
+    }
+}
+
+/// The mnemonic, or `CLASSnnn` (RFC 3597) for unassigned classes.
+impl std::fmt::Display for DnsClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DnsClass::IN => write!(f, "IN"),
+            DnsClass::CS => write!(f, "CS"),
+            DnsClass::CH => write!(f, "CH"),
+            DnsClass::HS => write!(f, "HS"),
+            DnsClass::NONE => write!(f, "NONE"),
+            DnsClass::ALL => write!(f, "ANY"),
+            DnsClass::UnknownClass(qclass) | DnsClass::OtherUsage(qclass) => {
+                write!(f, "CLASS{}", qclass)
+            }
+        }
+    }
+}
+
+/// Accepts mnemonics in any case, `CLASSnnn`, and `*` or `ALL` for `ANY`.
+impl std::str::FromStr for DnsClass {
+    type Err = UnknownMnemonic;
+
+    fn from_str(s: &str) -> Result<DnsClass, UnknownMnemonic> {
+        let upper = s.to_ascii_uppercase();
+        if let Some(Ok(qclass)) = upper.strip_prefix("CLASS").map(str::parse::<u16>) {
+            return Ok(DnsClass::from(qclass));
+        }
+        match upper.as_str() {
+            "IN" => Ok(DnsClass::IN),
+            "CS" => Ok(DnsClass::CS),
+            "CH" => Ok(DnsClass::CH),
+            "HS" => Ok(DnsClass::HS),
+            "NONE" => Ok(DnsClass::NONE),
+            "ANY" | "*" | "ALL" => Ok(DnsClass::ALL),
+            _ => Err(UnknownMnemonic(s.to_string())),
+        }
+    }
+}
+
+impl Serialize for DnsClass {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -148,6 +512,24 @@ mod tests {
         assert_eq!(flags.rcode, Rcode::NoError);
         assert!(!flags.aa);
         assert!(flags.rd);
+        assert_eq!(0x8180, u16::from(&flags));
+        assert_eq!(vec!["qr", "rd", "ra"], flags.names());
+    }
+
+    #[test]
+    fn test_parse_all_flags() {
+        let (_, flags) = parse_flags(&[0x28, 0x70]).unwrap();
+        assert_eq!(Opcode::Update, flags.opcode);
+        assert!(flags.z && flags.ad && flags.cd);
+        assert_eq!(vec!["z", "ad", "cd"], flags.names());
+
+        let (_, flags) = parse_flags(&[0x38, 0x00]).unwrap();
+        assert_eq!(Opcode::Other(7), flags.opcode);
+
+        for value in 0..=u16::MAX {
+            let (_, flags) = parse_flags(&value.to_be_bytes()).unwrap();
+            assert_eq!(value, u16::from(&flags));
+        }
     }
 
     #[test]
@@ -155,6 +537,7 @@ mod tests {
         let data: [u8; 2] = [0, 255];
         let (_, x) = parse_dnsclass(&data).unwrap();
         assert_eq!(DnsClass::ALL, x);
+        assert_eq!(255, u16::from(&x));
     }
 
     #[test]
@@ -162,6 +545,50 @@ mod tests {
         let data: [u8; 2] = [0, 33];
         let (_, x) = parse_dnstype(&data).unwrap();
         assert_eq!(DnsType::SRV, x);
+        assert_eq!(33, u16::from(&x));
     }
 
+    #[test]
+    fn test_dnstype_registry() {
+        for qtype in 0..=u16::MAX {
+            let x = DnsType::from(qtype);
+            assert_eq!(qtype, u16::from(&x));
+            assert_eq!(Ok(x.clone()), x.to_string().parse());
+        }
+        assert_eq!("HTTPS", DnsType::from(65).to_string());
+        assert_eq!("NSAP-PTR", DnsType::from(23).to_string());
+        assert_eq!("ANY", DnsType::ALL.to_string());
+        assert_eq!("TYPE65280", DnsType::from(65280).to_string());
+        assert_eq!(Ok(DnsType::HTTPS), "https".parse());
+        assert_eq!(Ok(DnsType::ALL), "*".parse());
+        assert_eq!(Ok(DnsType::A), "TYPE1".parse());
+        assert_eq!(
+            Err(UnknownMnemonic(String::from("BOGUS"))),
+            "BOGUS".parse::<DnsType>()
+        );
+        assert_eq!(
+            r#""TYPE999""#,
+            serde_json::to_string(&DnsType::from(999)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dnsclass_registry() {
+        assert_eq!("CLASS32769", DnsClass::from(0x8001).to_string());
+        assert_eq!(
+            (DnsClass::IN, true),
+            DnsClass::from(0x8001).split_mdns_bit()
+        );
+        assert_eq!(
+            (DnsClass::UnknownClass(0x8020), false),
+            DnsClass::from(0x8020).split_mdns_bit()
+        );
+        assert_eq!((DnsClass::CH, false), DnsClass::CH.split_mdns_bit());
+        assert_eq!("NONE", DnsClass::from(254).to_string());
+        assert_eq!("CLASS32", DnsClass::from(32).to_string());
+        assert_eq!(Ok(DnsClass::CH), "ch".parse());
+        assert_eq!(Ok(DnsClass::ALL), "ANY".parse());
+        assert_eq!(Ok(DnsClass::UnknownClass(32)), "CLASS32".parse());
+        assert!("XX".parse::<DnsClass>().is_err());
+    }
 }
@@ -0,0 +1,356 @@
+use crate::parse::dns::*;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+
+/// A DNS-SD service instance (RFC 6763), e.g. `Office._ipp._tcp.local`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ServiceInstance {
+    pub name: DomainName,
+    pub instance: String,
+    pub service_type: String,
+    pub domain: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<DomainName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    pub addresses: Vec<IpAddr>,
+    pub txt: BTreeMap<String, String>,
+}
+
+impl ServiceInstance {
+    /// Split `<instance>.<_service>.<_proto>.<domain>`, `None` for names
+    /// of another form.
+    fn from_name(name: &DomainName) -> Option<ServiceInstance> {
+        let labels: Vec<String> = name
+            .labels()
+            .map(|l| String::from_utf8_lossy(l).into_owned())
+            .collect();
+        if labels.len() < 4
+            || !labels[1].starts_with('_')
+            || !(labels[2].eq_ignore_ascii_case("_tcp") || labels[2].eq_ignore_ascii_case("_udp"))
+        {
+            return None;
+        }
+        Some(ServiceInstance {
+            name: name.clone(),
+            instance: labels[0].clone(),
+            service_type: format!("{}.{}", labels[1], labels[2]),
+            domain: labels[3..].join("."),
+            host: None,
+            port: None,
+            addresses: Vec::new(),
+            txt: BTreeMap::new(),
+        })
+    }
+}
+
+/// `"Office" _ipp._tcp.local printer.local:631 192.0.2.7 rp=ipp/print`
+impl std::fmt::Display for ServiceInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {}.{}",
+            self.instance, self.service_type, self.domain
+        )?;
+        if let Some(host) = &self.host {
+            write!(f, " {}", host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        for address in &self.addresses {
+            write!(f, " {}", address)?;
+        }
+        for (key, value) in &self.txt {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Assembles service instances from the PTR, SRV, TXT and A/AAAA records
+/// of mDNS responses.
+#[derive(Clone, Debug, Default)]
+pub struct ServiceInventory {
+    instances: Vec<ServiceInstance>,
+    index: HashMap<DomainName, usize>,
+    hosts: HashMap<DomainName, Vec<IpAddr>>,
+    // Instances and hosts changed since the last `add_message`.
+    changed: HashSet<DomainName>,
+    changed_hosts: HashSet<DomainName>,
+}
+
+impl ServiceInventory {
+    pub fn new() -> ServiceInventory {
+        ServiceInventory::default()
+    }
+
+    /// Add the resolved answer and additional records of a response and
+    /// return the instances that are new or changed by it. Queries and
+    /// probes, which carry proposed records in the authority section, are
+    /// ignored.
+    pub fn add_message(&mut self, dns: &DnsMessage) -> Vec<ServiceInstance> {
+        if dns.header.flags.qr != Qr::Response {
+            return Vec::new();
+        }
+        self.changed.clear();
+        self.changed_hosts.clear();
+        for rr in dns.answers.iter().chain(dns.arecords.iter()) {
+            self.add_record(rr);
+        }
+        let changed = self
+            .instances
+            .iter()
+            .filter(|instance| {
+                self.changed.contains(&instance.name)
+                    || instance
+                        .host
+                        .as_ref()
+                        .is_some_and(|host| self.changed_hosts.contains(host))
+            })
+            .map(|instance| self.service(instance))
+            .collect();
+        self.changed.clear();
+        self.changed_hosts.clear();
+        changed
+    }
+
+    pub fn add_record(&mut self, rr: &ResourceRecord) {
+        match &rr.record {
+            Some(RRecordTypes::PTR(ptr)) => {
+                // Service type enumeration points at types, not instances.
+                let first = rr.name.labels().next().unwrap_or_default();
+                if first.eq_ignore_ascii_case(b"_services") {
+                    return;
+                }
+                if rr.ttl == 0 {
+                    self.remove(&ptr.name);
+                } else {
+                    self.instance(&ptr.name);
+                }
+            }
+            Some(RRecordTypes::SRV(srv)) if rr.ttl != 0 => {
+                if let Some(instance) = self.instance(&rr.name) {
+                    let host = Some(srv.target.clone());
+                    if instance.host != host || instance.port != Some(srv.port) {
+                        instance.host = host;
+                        instance.port = Some(srv.port);
+                        self.changed.insert(rr.name.clone());
+                    }
+                }
+            }
+            Some(RRecordTypes::TXT(txt)) if rr.ttl != 0 => {
+                if let Some(instance) = self.instance(&rr.name) {
+                    let keys = txt_keys(txt);
+                    if instance.txt != keys {
+                        instance.txt = keys;
+                        self.changed.insert(rr.name.clone());
+                    }
+                }
+            }
+            Some(RRecordTypes::A(a)) => {
+                self.update_address(&rr.name, IpAddr::V4(a.address), rr.ttl)
+            }
+            Some(RRecordTypes::AAAA(aaaa)) => {
+                self.update_address(&rr.name, IpAddr::V6(aaaa.address), rr.ttl)
+            }
+            _ => {}
+        }
+    }
+
+    /// The instances in the order they were first seen, with the
+    /// addresses of their hosts.
+    pub fn services(&self) -> Vec<ServiceInstance> {
+        self.instances
+            .iter()
+            .map(|instance| self.service(instance))
+            .collect()
+    }
+
+    fn service(&self, instance: &ServiceInstance) -> ServiceInstance {
+        let mut instance = instance.clone();
+        if let Some(addresses) = instance.host.as_ref().and_then(|h| self.hosts.get(h)) {
+            instance.addresses = addresses.clone();
+        }
+        instance
+    }
+
+    fn instance(&mut self, name: &DomainName) -> Option<&mut ServiceInstance> {
+        let i = match self.index.get(name) {
+            Some(&i) => i,
+            None => {
+                let instance = ServiceInstance::from_name(name)?;
+                self.changed.insert(name.clone());
+                self.index.insert(name.clone(), self.instances.len());
+                self.instances.push(instance);
+                self.instances.len() - 1
+            }
+        };
+        self.instances.get_mut(i)
+    }
+
+    fn remove(&mut self, name: &DomainName) {
+        if let Some(i) = self.index.remove(name) {
+            self.instances.remove(i);
+            for index in self.index.values_mut() {
+                if *index > i {
+                    *index -= 1;
+                }
+            }
+        }
+    }
+
+    /// Add an address of `host`, or remove it on a goodbye (TTL 0).
+    fn update_address(&mut self, host: &DomainName, address: IpAddr, ttl: u32) {
+        if ttl == 0 {
+            if let Some(addresses) = self.hosts.get_mut(host) {
+                if let Some(i) = addresses.iter().position(|a| *a == address) {
+                    addresses.remove(i);
+                    self.changed_hosts.insert(host.clone());
+                }
+                if addresses.is_empty() {
+                    self.hosts.remove(host);
+                }
+            }
+            return;
+        }
+        let addresses = self.hosts.entry(host.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+            self.changed_hosts.insert(host.clone());
+        }
+    }
+}
+
+/// The `key=value` strings of a DNS-SD TXT record (RFC 6763 6.3). Keys
+/// without `=` are boolean attributes with an empty value, and only the
+/// first occurrence of a key counts.
+fn txt_keys(txt: &TxtRecord) -> BTreeMap<String, String> {
+    let mut keys = BTreeMap::new();
+    for string in &txt.strings {
+        let string = String::from_utf8_lossy(string);
+        let (key, value) = string.split_once('=').unwrap_or((&string, ""));
+        if !key.is_empty() {
+            keys.entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, ttl: u32, record: RRecordTypes) -> ResourceRecord<'static> {
+        let mut rr = ResourceRecord::new(name.parse().unwrap(), DnsClass::IN, ttl, record);
+        rr.cache_flush = true;
+        rr
+    }
+
+    fn ptr(name: &str) -> RRecordTypes {
+        RRecordTypes::PTR(PtrRecord {
+            name: name.parse().unwrap(),
+        })
+    }
+
+    fn announcement(ttl: u32) -> OwnedDnsMessage {
+        let flags = Flags {
+            qr: Qr::Response,
+            aa: true,
+            ..Default::default()
+        };
+        let srv = RRecordTypes::SRV(SrvRecord {
+            prio: 0,
+            weight: 0,
+            port: 631,
+            target: "printer.local".parse().unwrap(),
+        });
+        let txt = RRecordTypes::TXT(TxtRecord::from_strings(vec![
+            b"rp=ipp/print".to_vec(),
+            b"Color".to_vec(),
+            b"rp=ignored".to_vec(),
+        ]));
+        let a = RRecordTypes::A(ARecord {
+            address: "192.0.2.7".parse().unwrap(),
+        });
+        DnsMessageBuilder::new(0)
+            .flags(flags)
+            .answer(record(
+                "_services._dns-sd._udp.local",
+                ttl,
+                ptr("_ipp._tcp.local"),
+            ))
+            .answer(record(
+                "_ipp._tcp.local",
+                ttl,
+                ptr("Office._ipp._tcp.local"),
+            ))
+            .additional(record("Office._ipp._tcp.local", ttl, srv))
+            .additional(record("Office._ipp._tcp.local", ttl, txt))
+            .additional(record("printer.local", ttl, a))
+            .build()
+    }
+
+    #[test]
+    fn test_service_inventory() {
+        let mut inventory = ServiceInventory::new();
+        let wire = announcement(120).encode(true);
+        let (_rest, msg) = dns_message(&wire, true).unwrap();
+        assert!(msg.answers.iter().all(|rr| rr.cache_flush));
+        assert_eq!(inventory.add_message(&msg), inventory.services());
+        assert!(inventory.add_message(&msg).is_empty());
+        let services = inventory.services();
+        assert_eq!(1, services.len());
+        assert_eq!(
+            r#""Office" _ipp._tcp.local printer.local:631 192.0.2.7 Color= rp=ipp/print"#,
+            services[0].to_string()
+        );
+        assert_eq!("_ipp._tcp", services[0].service_type);
+
+        assert!(inventory.add_message(&announcement(0)).is_empty());
+        assert_eq!(Vec::<ServiceInstance>::new(), inventory.services());
+        assert!(inventory.hosts.is_empty());
+    }
+
+    #[test]
+    fn test_address_changes() {
+        let mut inventory = ServiceInventory::new();
+        inventory.add_message(&announcement(120));
+        let address = |ttl, address: &str| {
+            let a = RRecordTypes::A(ARecord {
+                address: address.parse().unwrap(),
+            });
+            let flags = Flags {
+                qr: Qr::Response,
+                ..Default::default()
+            };
+            DnsMessageBuilder::new(0)
+                .flags(flags)
+                .answer(record("printer.local", ttl, a))
+                .build()
+        };
+        let changed = inventory.add_message(&address(120, "192.0.2.8"));
+        assert_eq!(1, changed.len());
+        assert_eq!(
+            vec![IpAddr::from([192, 0, 2, 7]), IpAddr::from([192, 0, 2, 8])],
+            changed[0].addresses
+        );
+        let changed = inventory.add_message(&address(0, "192.0.2.7"));
+        assert_eq!(vec![IpAddr::from([192, 0, 2, 8])], changed[0].addresses);
+        assert!(inventory.add_message(&address(0, "192.0.2.7")).is_empty());
+        inventory.add_message(&address(0, "192.0.2.8"));
+        assert!(inventory.hosts.is_empty());
+        assert!(inventory.services()[0].addresses.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_queries() {
+        let mut msg = announcement(120);
+        msg.header.flags.qr = Qr::Query;
+        let mut inventory = ServiceInventory::new();
+        inventory.add_message(&msg);
+        assert!(inventory.services().is_empty());
+    }
+}
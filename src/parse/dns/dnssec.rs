@@ -0,0 +1,499 @@
+use crate::parse::dns::*;
+use data_encoding::{BASE32HEX_NOPAD, BASE64};
+use nom::*;
+use serde::Serializer;
+use serde_derive::Serialize;
+
+pub(crate) fn serialize_base64<S: Serializer>(
+    bytes: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64.encode(bytes))
+}
+
+/// A DNSSEC timestamp as `YYYYMMDDHHmmSS` (RFC 4034 3.2).
+pub fn format_dnssec_time(time: u32) -> String {
+    let days = i64::from(time / 86400);
+    let secs = time % 86400;
+    // Civil date from days since 1970-01-01, Howard Hinnant's algorithm.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn serialize_dnssec_time<S: Serializer>(time: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_dnssec_time(*time))
+}
+
+/// Decode NSEC/NSEC3 type bitmaps (RFC 4034 4.1.2).
+pub(crate) fn parse_type_bitmaps(mut i: &[u8]) -> Option<Vec<DnsType>> {
+    let mut types = Vec::new();
+    while !i.is_empty() {
+        let window = u16::from(*i.first()?);
+        let len = usize::from(*i.get(1)?);
+        if len == 0 || len > 32 {
+            return None;
+        }
+        let bitmap = i.get(2..2 + len)?;
+        for (n, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(DnsType::from(window << 8 | (n as u16) << 3 | bit));
+                }
+            }
+        }
+        i = &i[2 + len..];
+    }
+    Some(types)
+}
+
+/// Encode `types` as NSEC/NSEC3 type bitmaps.
+pub(crate) fn encode_type_bitmaps(types: &[DnsType]) -> Vec<u8> {
+    let mut values: Vec<u16> = types.iter().map(u16::from).collect();
+    values.sort_unstable();
+    values.dedup();
+    let mut out = Vec::new();
+    let mut values = values.into_iter().peekable();
+    while let Some(&first) = values.peek() {
+        let window = first >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut len = 0;
+        while let Some(value) = values.next_if(|v| v >> 8 == window) {
+            let low = usize::from(value & 0xff);
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            len = low / 8 + 1;
+        }
+        out.push(window as u8);
+        out.push(len as u8);
+        out.extend_from_slice(&bitmap[..len]);
+    }
+    out
+}
+
+fn fmt_types(f: &mut std::fmt::Formatter, types: &[DnsType]) -> std::fmt::Result {
+    for rrtype in types.iter() {
+        write!(f, " {}", rrtype)?;
+    }
+    Ok(())
+}
+
+fn fmt_salt(f: &mut std::fmt::Formatter, salt: &[u8]) -> std::fmt::Result {
+    if salt.is_empty() {
+        write!(f, "-")
+    } else {
+        write!(f, "{}", hex::encode_upper(salt))
+    }
+}
+
+/// Key tag of a DNSKEY rdata (RFC 4034 appendix B).
+pub fn key_tag(rdata: &[u8]) -> u16 {
+    if rdata.get(3) == Some(&1) {
+        // RSA/MD5: the low 16 bits of the modulus.
+        return match rdata.len() {
+            len if len >= 7 => u16::from_be_bytes([rdata[len - 3], rdata[len - 2]]),
+            _ => 0,
+        };
+    }
+    let mut ac: u32 = 0;
+    for (i, b) in rdata.iter().enumerate() {
+        ac += if i & 1 == 0 {
+            u32::from(*b) << 8
+        } else {
+            u32::from(*b)
+        };
+    }
+    ac += (ac >> 16) & 0xffff;
+    (ac & 0xffff) as u16
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DnskeyRecord {
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub key_tag: u16,
+    #[serde(serialize_with = "serialize_base64")]
+    pub public_key: Vec<u8>,
+}
+
+impl DnskeyRecord {
+    named!(parse_rdata<&[u8], (u16, u8, u8, &[u8])>, do_parse!(
+        flags: be_u16 >>
+        protocol: be_u8 >>
+        algorithm: be_u8 >>
+        public_key: rest >>
+        ((flags, protocol, algorithm, public_key))
+    ));
+
+    pub fn new(
+        rr: &ResourceRecord,
+        full_dns_message: &[u8],
+    ) -> Result<DnskeyRecord, DnsParseError> {
+        let (_, (flags, protocol, algorithm, public_key)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(DnskeyRecord {
+            flags,
+            protocol,
+            algorithm,
+            key_tag: key_tag(&rr.rdata),
+            public_key: public_key.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for DnskeyRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.flags,
+            self.protocol,
+            self.algorithm,
+            BASE64.encode(&self.public_key)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RrsigRecord {
+    pub type_covered: DnsType,
+    pub algorithm: u8,
+    pub labels: u8,
+    pub original_ttl: u32,
+    #[serde(serialize_with = "serialize_dnssec_time")]
+    pub expiration: u32,
+    #[serde(serialize_with = "serialize_dnssec_time")]
+    pub inception: u32,
+    pub key_tag: u16,
+    pub signer_name: DomainName,
+    #[serde(serialize_with = "serialize_base64")]
+    pub signature: Vec<u8>,
+}
+
+impl RrsigRecord {
+    #[rustfmt::skip]
+    named!(parse_fields<&[u8], (DnsType, u8, u8, u32, u32, u32, u16)>, do_parse!(
+        type_covered: parse_dnstype >>
+        algorithm: be_u8 >>
+        labels: be_u8 >>
+        original_ttl: be_u32 >>
+        expiration: be_u32 >>
+        inception: be_u32 >>
+        key_tag: be_u16 >>
+        ((type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<RrsigRecord, DnsParseError> {
+        let (i, (type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag)) =
+            Self::parse_fields(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let (signature, signer_chain) =
+            parse_compressed_chain(i).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let signer_name = signer_chain.resolve_name(full_dns_message)?;
+        Ok(RrsigRecord {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature: signature.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for RrsigRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {}",
+            self.type_covered,
+            self.algorithm,
+            self.labels,
+            self.original_ttl,
+            format_dnssec_time(self.expiration),
+            format_dnssec_time(self.inception),
+            self.key_tag,
+            self.signer_name,
+            BASE64.encode(&self.signature)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DsRecord {
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    #[serde(serialize_with = "serialize_hex")]
+    pub digest: Vec<u8>,
+}
+
+impl DsRecord {
+    named!(parse_rdata<&[u8], (u16, u8, u8, &[u8])>, do_parse!(
+        key_tag: be_u16 >>
+        algorithm: be_u8 >>
+        digest_type: be_u8 >>
+        digest: rest >>
+        ((key_tag, algorithm, digest_type, digest))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<DsRecord, DnsParseError> {
+        let (_, (key_tag, algorithm, digest_type, digest)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(DsRecord {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest: digest.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for DsRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.key_tag,
+            self.algorithm,
+            self.digest_type,
+            hex::encode_upper(&self.digest)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NsecRecord {
+    pub next_domain_name: DomainName,
+    pub types: Vec<DnsType>,
+}
+
+impl NsecRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<NsecRecord, DnsParseError> {
+        let (bitmaps, next_chain) =
+            parse_compressed_chain(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let next_domain_name = next_chain.resolve_name(full_dns_message)?;
+        let types =
+            parse_type_bitmaps(bitmaps).ok_or_else(|| rr.invalid_rdata(full_dns_message))?;
+        Ok(NsecRecord {
+            next_domain_name,
+            types,
+        })
+    }
+}
+
+impl std::fmt::Display for NsecRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.next_domain_name)?;
+        fmt_types(f, &self.types)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Nsec3Record {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    #[serde(serialize_with = "serialize_hex")]
+    pub salt: Vec<u8>,
+    #[serde(serialize_with = "serialize_base32hex")]
+    pub next_hashed_owner: Vec<u8>,
+    pub types: Vec<DnsType>,
+}
+
+fn serialize_base32hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE32HEX_NOPAD.encode(bytes))
+}
+
+impl Nsec3Record {
+    #[rustfmt::skip]
+    named!(parse_rdata<&[u8], (u8, u8, u16, &[u8], &[u8], &[u8])>, do_parse!(
+        hash_algorithm: be_u8 >>
+        flags: be_u8 >>
+        iterations: be_u16 >>
+        salt: length_bytes!(be_u8) >>
+        next_hashed_owner: length_bytes!(be_u8) >>
+        bitmaps: rest >>
+        ((hash_algorithm, flags, iterations, salt, next_hashed_owner, bitmaps))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<Nsec3Record, DnsParseError> {
+        let (_, (hash_algorithm, flags, iterations, salt, next_hashed_owner, bitmaps)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let types =
+            parse_type_bitmaps(bitmaps).ok_or_else(|| rr.invalid_rdata(full_dns_message))?;
+        Ok(Nsec3Record {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt: salt.to_vec(),
+            next_hashed_owner: next_hashed_owner.to_vec(),
+            types,
+        })
+    }
+}
+
+impl std::fmt::Display for Nsec3Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
+        )?;
+        fmt_salt(f, &self.salt)?;
+        write!(f, " {}", BASE32HEX_NOPAD.encode(&self.next_hashed_owner))?;
+        fmt_types(f, &self.types)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Nsec3ParamRecord {
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    #[serde(serialize_with = "serialize_hex")]
+    pub salt: Vec<u8>,
+}
+
+impl Nsec3ParamRecord {
+    named!(parse_rdata<&[u8], (u8, u8, u16, &[u8])>, do_parse!(
+        hash_algorithm: be_u8 >>
+        flags: be_u8 >>
+        iterations: be_u16 >>
+        salt: length_bytes!(be_u8) >>
+        ((hash_algorithm, flags, iterations, salt))
+    ));
+
+    pub fn new(
+        rr: &ResourceRecord,
+        full_dns_message: &[u8],
+    ) -> Result<Nsec3ParamRecord, DnsParseError> {
+        let (_, (hash_algorithm, flags, iterations, salt)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(Nsec3ParamRecord {
+            hash_algorithm,
+            flags,
+            iterations,
+            salt: salt.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for Nsec3ParamRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
+        )?;
+        fmt_salt(f, &self.salt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::dns::resource_record::tests::resolved;
+
+    const ROOT_KSK: &str = "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq\
+                            7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnI\
+                            DdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb\
+                            9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTId\
+                            sIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qih\
+                            ylGa8subX2Nn6UwNR1AkUTV74bU=";
+
+    #[test]
+    fn test_dnskey_key_tag() {
+        let mut rdata = vec![0x01, 0x01, 3, 8];
+        rdata.extend(BASE64.decode(ROOT_KSK.as_bytes()).unwrap());
+        let record = resolved(48, &rdata);
+        let dnskey = match &record {
+            RRecordTypes::DNSKEY(dnskey) => dnskey,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(20326, dnskey.key_tag);
+        assert_eq!(format!("257 3 8 {}", ROOT_KSK), record.to_string());
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.starts_with(r#"{"DNSKEY":{"flags":257,"protocol":3,"algorithm":8,"key_tag":20326,"public_key":"AwEAAaz/"#));
+    }
+
+    #[test]
+    fn test_rrsig() {
+        let mut rdata = vec![0, 1, 13, 2, 0, 0, 0x0e, 0x10];
+        rdata.extend_from_slice(&1_700_000_000u32.to_be_bytes());
+        rdata.extend_from_slice(&1_698_796_800u32.to_be_bytes());
+        rdata.extend_from_slice(&[0x30, 0x39]);
+        rdata.extend_from_slice(b"\x07example\x03com\x00");
+        rdata.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let record = resolved(46, &rdata);
+        assert_eq!(
+            "A 13 2 3600 20231114221320 20231101000000 12345 example.com 3q2+7w==",
+            record.to_string()
+        );
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(r#""expiration":"20231114221320""#));
+    }
+
+    #[test]
+    fn test_ds() {
+        let record = resolved(43, &[0x4f, 0x66, 8, 2, 0xe0, 0x6d, 0x44]);
+        assert_eq!("20326 8 2 E06D44", record.to_string());
+    }
+
+    #[test]
+    fn test_nsec() {
+        let mut rdata = b"\x04host\x07example\x03com\x00".to_vec();
+        rdata.extend_from_slice(&[0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03]);
+        rdata.extend_from_slice(&[0x04, 0x1b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        rdata.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x20]);
+        let record = resolved(47, &rdata);
+        assert_eq!(
+            "host.example.com A MX RRSIG NSEC TYPE1234",
+            record.to_string()
+        );
+        if let RRecordTypes::NSEC(nsec) = &record {
+            assert_eq!(rdata[18..], encode_type_bitmaps(&nsec.types)[..]);
+        } else {
+            panic!("not NSEC");
+        }
+
+        let record = resolved(47, b"\x00\x00\x00");
+        assert!(matches!(record, RRecordTypes::Malformed(_)));
+    }
+
+    #[test]
+    fn test_nsec3() {
+        let mut rdata = vec![1, 1, 0, 12, 4, 0xaa, 0xbb, 0xcc, 0xdd, 5];
+        rdata.extend_from_slice(&[0x2b, 0x3c, 0x4d, 0x5e, 0x6f]);
+        rdata.extend_from_slice(&[0x00, 0x01, 0x40]);
+        let record = resolved(50, &rdata);
+        assert_eq!("1 1 12 AABBCCDD 5CU4QNJF A", record.to_string());
+
+        let record = resolved(51, &[1, 0, 0, 0, 0]);
+        assert_eq!("1 0 0 -", record.to_string());
+    }
+
+    #[test]
+    fn test_format_dnssec_time() {
+        assert_eq!("19700101000000", format_dnssec_time(0));
+        assert_eq!("20000229235959", format_dnssec_time(951_868_799));
+        assert_eq!("21060207062815", format_dnssec_time(u32::MAX));
+    }
+}
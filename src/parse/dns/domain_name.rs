@@ -0,0 +1,309 @@
+use crate::parse::dns::*;
+use serde::{Serialize, Serializer};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+/// Longest allowed label (RFC 1035).
+pub const LABEL_MAX_LEN: usize = 63;
+
+/// Longest allowed name in wire format, length octets included (RFC 1035).
+pub const NAME_MAX_LEN: usize = 255;
+
+/// A domain name, kept as its raw labels.
+///
+/// Labels are binary safe. Comparison and hashing ignore ASCII case, and
+/// `Display` gives the RFC 1035 presentation format.
+#[derive(Clone, Debug, Default)]
+pub struct DomainName {
+    labels: Vec<Vec<u8>>,
+}
+
+impl DomainName {
+    /// The root name, `.`.
+    pub fn root() -> DomainName {
+        DomainName::default()
+    }
+
+    /// Build a name from its labels, most specific first.
+    pub fn from_labels<I, L>(labels: I) -> Result<DomainName, NameError>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut name = DomainName::root();
+        for label in labels {
+            name.push_label(label.as_ref())?;
+        }
+        Ok(name)
+    }
+
+    pub(crate) fn push_label(&mut self, label: &[u8]) -> Result<(), NameError> {
+        if label.is_empty() {
+            return Err(NameError::EmptyLabel);
+        }
+        if label.len() > LABEL_MAX_LEN {
+            return Err(NameError::LabelTooLong(label.len().min(255) as u8));
+        }
+        if self.wire_len() + label.len() + 1 > NAME_MAX_LEN {
+            return Err(NameError::NameTooLong);
+        }
+        self.labels.push(label.to_vec());
+        Ok(())
+    }
+
+    /// Labels from `decompress_name`, which already checked the limits.
+    pub(crate) fn from_decoded(labels: &[&[u8]]) -> DomainName {
+        DomainName {
+            labels: labels.iter().map(|l| l.to_vec()).collect(),
+        }
+    }
+
+    /// Decode a name in wire format, compression pointers are followed
+    /// within `data`.
+    pub fn from_wire(data: &[u8]) -> Result<DomainName, DnsParseError> {
+        Ok(DomainName::from_decoded(&decompress_name(data, 0)?))
+    }
+
+    /// The uncompressed wire format.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut wire = Vec::with_capacity(self.wire_len());
+        for label in self.labels.iter() {
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+        wire
+    }
+
+    /// Length of the uncompressed wire format.
+    pub fn wire_len(&self) -> usize {
+        self.labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1
+    }
+
+    /// The labels, most specific first. The root label is not included.
+    pub fn labels(&self) -> impl DoubleEndedIterator<Item = &[u8]> + ExactSizeIterator {
+        self.labels.iter().map(Vec::as_slice)
+    }
+
+    pub fn label_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// The name with its first label removed, `None` for the root.
+    pub fn parent(&self) -> Option<DomainName> {
+        if self.is_root() {
+            None
+        } else {
+            Some(DomainName {
+                labels: self.labels[1..].to_vec(),
+            })
+        }
+    }
+
+    /// True if this name is `other` or below it.
+    pub fn is_subdomain_of(&self, other: &DomainName) -> bool {
+        self.labels.len() >= other.labels.len()
+            && self
+                .labels
+                .iter()
+                .rev()
+                .zip(other.labels.iter().rev())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl PartialEq for DomainName {
+    fn eq(&self, other: &DomainName) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(other.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.labels.len());
+        for label in self.labels.iter() {
+            state.write_usize(label.len());
+            for b in label.iter() {
+                state.write_u8(b.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+/// Append `label` to `out` in RFC 1035 presentation format.
+///
+/// Special characters are escaped as `\X`, anything outside printable
+/// ASCII as `\DDD`, so the result always maps back to the same bytes.
+pub(crate) fn escape_label(label: &[u8], out: &mut String) {
+    for &b in label {
+        match b {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x21..=0x7e => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\{:03}", b);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DomainName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+        let mut name = String::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                name.push('.');
+            }
+            escape_label(label, &mut name);
+        }
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse a name in presentation format, with `\X` and `\DDD` escapes.
+/// A trailing dot is optional.
+impl std::str::FromStr for DomainName {
+    type Err = NameError;
+
+    fn from_str(s: &str) -> Result<DomainName, NameError> {
+        let mut name = DomainName::root();
+        if s == "." {
+            return Ok(name);
+        }
+        let mut label = Vec::new();
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'.' => {
+                    name.push_label(&label)?;
+                    label.clear();
+                }
+                b'\\' => match bytes.next() {
+                    Some(d) if d.is_ascii_digit() => {
+                        let digits = [Some(d), bytes.next(), bytes.next()];
+                        let mut value = 0u16;
+                        for digit in digits.iter() {
+                            match digit {
+                                Some(d) if d.is_ascii_digit() => {
+                                    value = value * 10 + u16::from(d - b'0')
+                                }
+                                _ => return Err(NameError::BadEscape),
+                            }
+                        }
+                        if value > 255 {
+                            return Err(NameError::BadEscape);
+                        }
+                        label.push(value as u8);
+                    }
+                    Some(c) => label.push(c),
+                    None => return Err(NameError::BadEscape),
+                },
+                _ => label.push(b),
+            }
+        }
+        if !label.is_empty() {
+            name.push_label(&label)?;
+        } else if !s.ends_with('.') {
+            return Err(NameError::EmptyLabel);
+        }
+        Ok(name)
+    }
+}
+
+impl Serialize for DomainName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn name(s: &str) -> DomainName {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_presentation_round_trip() {
+        let n = DomainName::from_labels([&b"a.b"[..], b"\xff\x00 \\", b"com"]).unwrap();
+        let text = n.to_string();
+        assert_eq!(r#"a\.b.\255\000\032\\.com"#, text);
+        assert_eq!(n, name(&text));
+        assert_eq!(3, n.label_count());
+        assert_eq!(".", DomainName::root().to_string());
+        assert_eq!(DomainName::root(), name("."));
+        assert_eq!(name("example.com"), name("example.com."));
+    }
+
+    #[test]
+    fn test_bad_presentation() {
+        assert_eq!(Err(NameError::EmptyLabel), "a..b".parse::<DomainName>());
+        assert_eq!(Err(NameError::EmptyLabel), "".parse::<DomainName>());
+        assert_eq!(Err(NameError::BadEscape), "a\\25".parse::<DomainName>());
+        assert_eq!(Err(NameError::BadEscape), "a\\256".parse::<DomainName>());
+        assert_eq!(
+            Err(NameError::LabelTooLong(64)),
+            "a".repeat(64).parse::<DomainName>()
+        );
+        let long = vec!["a".repeat(63); 4].join(".");
+        assert_eq!(Err(NameError::NameTooLong), long.parse::<DomainName>());
+    }
+
+    #[test]
+    fn test_wire_round_trip() {
+        let n = name("www.Example.com");
+        let wire = n.to_wire();
+        assert_eq!(b"\x03www\x07Example\x03com\x00".to_vec(), wire);
+        assert_eq!(wire.len(), n.wire_len());
+        assert_eq!(n, DomainName::from_wire(&wire).unwrap());
+        assert_eq!(vec![0], DomainName::root().to_wire());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert_eq!(name("WWW.Example.COM"), name("www.example.com"));
+        assert_ne!(name("www.example.com"), name("www.example.org"));
+        let mut set = HashSet::new();
+        set.insert(name("WWW.Example.COM"));
+        assert!(set.contains(&name("www.example.com")));
+        assert_eq!("WWW.Example.COM", name("WWW.Example.COM").to_string());
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let n = name("www.example.com");
+        assert!(n.is_subdomain_of(&name("EXAMPLE.com")));
+        assert!(n.is_subdomain_of(&n));
+        assert!(n.is_subdomain_of(&DomainName::root()));
+        assert!(!n.is_subdomain_of(&name("ample.com")));
+        assert!(!name("com").is_subdomain_of(&n));
+        assert_eq!(Some(name("example.com")), n.parent());
+        assert_eq!(None, DomainName::root().parent());
+        let labels: Vec<_> = n.labels().collect();
+        assert_eq!(vec![&b"www"[..], b"example", b"com"], labels);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let json = serde_json::to_string(&name("a\\.b.com")).unwrap();
+        assert_eq!(r#""a\\.b.com""#, json);
+    }
+}
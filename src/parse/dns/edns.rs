@@ -0,0 +1,479 @@
+use crate::parse::dns::*;
+use nom::*;
+use serde_derive::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OptionCookie {
+    pub client_cookie: String,
+    pub server_cookie: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OptionNsid {
+    pub hex: String,
+    pub text: String,
+}
+
+/// EDNS Client Subnet (RFC 7871 6).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OptionClientSubnet {
+    pub family: u16,
+    pub source_prefix: u8,
+    pub scope_prefix: u8,
+    pub address: IpAddr,
+}
+
+/// Extended DNS Error (RFC 8914).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OptionExtendedError {
+    pub info_code: u16,
+    pub purpose: Option<&'static str>,
+    pub extra_text: String,
+}
+
+impl std::fmt::Display for OptionExtendedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.info_code)?;
+        if let Some(purpose) = self.purpose {
+            write!(f, " ({})", purpose)?;
+        }
+        if !self.extra_text.is_empty() {
+            write!(f, ": {:?}", self.extra_text)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of an Extended DNS Error info-code.
+pub fn ede_purpose(info_code: u16) -> Option<&'static str> {
+    let purpose = match info_code {
+        0 => "Other Error",
+        1 => "Unsupported DNSKEY Algorithm",
+        2 => "Unsupported DS Digest Type",
+        3 => "Stale Answer",
+        4 => "Forged Answer",
+        5 => "DNSSEC Indeterminate",
+        6 => "DNSSEC Bogus",
+        7 => "Signature Expired",
+        8 => "Signature Not Yet Valid",
+        9 => "DNSKEY Missing",
+        10 => "RRSIGs Missing",
+        11 => "No Zone Key Bit Set",
+        12 => "NSEC Missing",
+        13 => "Cached Error",
+        14 => "Not Ready",
+        15 => "Blocked",
+        16 => "Censored",
+        17 => "Filtered",
+        18 => "Prohibited",
+        19 => "Stale NXDomain Answer",
+        20 => "Not Authoritative",
+        21 => "Not Supported",
+        22 => "No Reachable Authority",
+        23 => "Network Error",
+        24 => "Invalid Data",
+        25 => "Signature Expired before Valid",
+        26 => "Too Early",
+        27 => "Unsupported NSEC3 Iterations Value",
+        28 => "Unable to conform to policy",
+        29 => "Synthesized",
+        30 => "Invalid Query Type",
+        _ => return None,
+    };
+    Some(purpose)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum EdnsOption {
+    NSID(OptionNsid),
+    DAU(Vec<u8>),
+    DHU(Vec<u8>),
+    N3U(Vec<u8>),
+    ECS(OptionClientSubnet),
+    EXPIRE(Option<u32>),
+    COOKIE(OptionCookie),
+    KEEPALIVE(Option<u16>),
+    PADDING(u16),
+    CHAIN(DomainName),
+    KEYTAG(Vec<u16>),
+    EDE(OptionExtendedError),
+    REPORTCHANNEL(DomainName),
+    Unknown {
+        code: u16,
+        #[serde(serialize_with = "serialize_hex")]
+        data: Vec<u8>,
+    },
+}
+
+/// An uncompressed name filling all of `data`.
+fn option_name(data: &[u8]) -> Option<DomainName> {
+    DomainName::from_wire(data)
+        .ok()
+        .filter(|name| name.wire_len() == data.len())
+}
+
+impl EdnsOption {
+    /// Decode the data of option `code`. Data that does not fit the
+    /// option is kept as `Unknown`.
+    pub fn new(code: u16, data: &[u8]) -> EdnsOption {
+        Self::decode(code, data).unwrap_or_else(|| EdnsOption::Unknown {
+            code,
+            data: data.to_vec(),
+        })
+    }
+
+    fn decode(code: u16, data: &[u8]) -> Option<EdnsOption> {
+        let option = match code {
+            3 => EdnsOption::NSID(OptionNsid {
+                hex: hex::encode(data),
+                text: String::from_utf8_lossy(data).to_string(),
+            }),
+            5 => EdnsOption::DAU(data.to_vec()),
+            6 => EdnsOption::DHU(data.to_vec()),
+            7 => EdnsOption::N3U(data.to_vec()),
+            8 if data.len() >= 4 => {
+                let family = u16::from_be_bytes([data[0], data[1]]);
+                let (source_prefix, scope_prefix) = (data[2], data[3]);
+                let addr = &data[4..];
+                if addr.len() != usize::from(source_prefix).div_ceil(8) {
+                    return None;
+                }
+                let address = match family {
+                    1 if source_prefix <= 32 => {
+                        let mut octets = [0u8; 4];
+                        octets[..addr.len()].copy_from_slice(addr);
+                        IpAddr::V4(Ipv4Addr::from(octets))
+                    }
+                    2 if source_prefix <= 128 => {
+                        let mut octets = [0u8; 16];
+                        octets[..addr.len()].copy_from_slice(addr);
+                        IpAddr::V6(Ipv6Addr::from(octets))
+                    }
+                    _ => return None,
+                };
+                EdnsOption::ECS(OptionClientSubnet {
+                    family,
+                    source_prefix,
+                    scope_prefix,
+                    address,
+                })
+            }
+            9 if data.is_empty() => EdnsOption::EXPIRE(None),
+            9 if data.len() == 4 => EdnsOption::EXPIRE(Some(u32::from_be_bytes([
+                data[0], data[1], data[2], data[3],
+            ]))),
+            10 if data.len() == 8 || (16..=40).contains(&data.len()) => {
+                let client_cookie = hex::encode(&data[0..8]);
+                let server_cookie = if data.len() >= 16 {
+                    hex::encode(&data[8..])
+                } else {
+                    String::from("<MISSING>")
+                };
+                EdnsOption::COOKIE(OptionCookie {
+                    client_cookie,
+                    server_cookie,
+                })
+            }
+            11 if data.is_empty() => EdnsOption::KEEPALIVE(None),
+            11 if data.len() == 2 => {
+                EdnsOption::KEEPALIVE(Some(u16::from_be_bytes([data[0], data[1]])))
+            }
+            12 => EdnsOption::PADDING(data.len() as u16),
+            13 => EdnsOption::CHAIN(option_name(data)?),
+            14 if data.len() % 2 == 0 => EdnsOption::KEYTAG(
+                data.chunks(2)
+                    .map(|t| u16::from_be_bytes([t[0], t[1]]))
+                    .collect(),
+            ),
+            15 if data.len() >= 2 => {
+                let info_code = u16::from_be_bytes([data[0], data[1]]);
+                EdnsOption::EDE(OptionExtendedError {
+                    info_code,
+                    purpose: ede_purpose(info_code),
+                    extra_text: String::from_utf8_lossy(&data[2..]).to_string(),
+                })
+            }
+            18 => EdnsOption::REPORTCHANNEL(option_name(data)?),
+            _ => return None,
+        };
+        Some(option)
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::NSID(_) => 3,
+            EdnsOption::DAU(_) => 5,
+            EdnsOption::DHU(_) => 6,
+            EdnsOption::N3U(_) => 7,
+            EdnsOption::ECS(_) => 8,
+            EdnsOption::EXPIRE(_) => 9,
+            EdnsOption::COOKIE(_) => 10,
+            EdnsOption::KEEPALIVE(_) => 11,
+            EdnsOption::PADDING(_) => 12,
+            EdnsOption::CHAIN(_) => 13,
+            EdnsOption::KEYTAG(_) => 14,
+            EdnsOption::EDE(_) => 15,
+            EdnsOption::REPORTCHANNEL(_) => 18,
+            EdnsOption::Unknown { code, .. } => *code,
+        }
+    }
+
+    /// The option data in wire format.
+    pub(crate) fn to_wire(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            EdnsOption::NSID(nsid) => out.extend(hex::decode(&nsid.hex).unwrap_or_default()),
+            EdnsOption::DAU(algs) | EdnsOption::DHU(algs) | EdnsOption::N3U(algs) => {
+                out.extend_from_slice(algs)
+            }
+            EdnsOption::ECS(ecs) => {
+                out.extend_from_slice(&ecs.family.to_be_bytes());
+                out.push(ecs.source_prefix);
+                out.push(ecs.scope_prefix);
+                let octets = match ecs.address {
+                    IpAddr::V4(addr) => addr.octets().to_vec(),
+                    IpAddr::V6(addr) => addr.octets().to_vec(),
+                };
+                let len = usize::from(ecs.source_prefix).div_ceil(8);
+                out.extend_from_slice(&octets[..len.min(octets.len())]);
+            }
+            EdnsOption::EXPIRE(expire) => {
+                if let Some(expire) = expire {
+                    out.extend_from_slice(&expire.to_be_bytes());
+                }
+            }
+            EdnsOption::COOKIE(cookie) => {
+                out.extend(hex::decode(&cookie.client_cookie).unwrap_or_default());
+                out.extend(hex::decode(&cookie.server_cookie).unwrap_or_default());
+            }
+            EdnsOption::KEEPALIVE(timeout) => {
+                if let Some(timeout) = timeout {
+                    out.extend_from_slice(&timeout.to_be_bytes());
+                }
+            }
+            EdnsOption::PADDING(len) => out.resize(usize::from(*len), 0),
+            EdnsOption::CHAIN(name) | EdnsOption::REPORTCHANNEL(name) => out.extend(name.to_wire()),
+            EdnsOption::KEYTAG(tags) => {
+                for tag in tags.iter() {
+                    out.extend_from_slice(&tag.to_be_bytes());
+                }
+            }
+            EdnsOption::EDE(ede) => {
+                out.extend_from_slice(&ede.info_code.to_be_bytes());
+                out.extend_from_slice(ede.extra_text.as_bytes());
+            }
+            EdnsOption::Unknown { data, .. } => out.extend_from_slice(data),
+        }
+        out
+    }
+}
+
+fn fmt_list<T: std::fmt::Display>(f: &mut std::fmt::Formatter, items: &[T]) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for EdnsOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EdnsOption::NSID(nsid) => write!(f, "NSID={} ({:?})", nsid.hex, nsid.text),
+            EdnsOption::DAU(algs) => write!(f, "DAU=").and_then(|_| fmt_list(f, algs)),
+            EdnsOption::DHU(algs) => write!(f, "DHU=").and_then(|_| fmt_list(f, algs)),
+            EdnsOption::N3U(algs) => write!(f, "N3U=").and_then(|_| fmt_list(f, algs)),
+            EdnsOption::ECS(ecs) => write!(
+                f,
+                "ECS={}/{}/{}",
+                ecs.address, ecs.source_prefix, ecs.scope_prefix
+            ),
+            EdnsOption::EXPIRE(None) => write!(f, "EXPIRE"),
+            EdnsOption::EXPIRE(Some(expire)) => write!(f, "EXPIRE={}", expire),
+            EdnsOption::COOKIE(cookie) => match cookie.server_cookie.as_str() {
+                "<MISSING>" => write!(f, "COOKIE={}", cookie.client_cookie),
+                server => write!(f, "COOKIE={}{}", cookie.client_cookie, server),
+            },
+            EdnsOption::KEEPALIVE(None) => write!(f, "KEEPALIVE"),
+            EdnsOption::KEEPALIVE(Some(timeout)) => {
+                write!(f, "KEEPALIVE={}.{}s", timeout / 10, timeout % 10)
+            }
+            EdnsOption::PADDING(len) => write!(f, "PADDING={}", len),
+            EdnsOption::CHAIN(name) => write!(f, "CHAIN={}", name),
+            EdnsOption::KEYTAG(tags) => write!(f, "KEY-TAG=").and_then(|_| fmt_list(f, tags)),
+            EdnsOption::EDE(ede) => write!(f, "EDE={}", ede),
+            EdnsOption::REPORTCHANNEL(name) => write!(f, "REPORT-CHANNEL={}", name),
+            EdnsOption::Unknown { code, data } => {
+                write!(f, "OPT{}={}", code, hex::encode(data))
+            }
+        }
+    }
+}
+
+pub(crate) fn parse_edns_option(i: &[u8]) -> IResult<&[u8], EdnsOption> {
+    let (i, code) = be_u16(i)?;
+    let (i, len) = be_u16(i)?;
+    let (i, data) = take!(i, len)?;
+    Ok((i, EdnsOption::new(code, data)))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OptRecord {
+    pub udp_payload_size: u16,
+    pub e_rcode: u8,
+    pub version: u8,
+    pub opt_do: u8,
+    pub z: u16,
+    pub options: Vec<EdnsOption>,
+}
+
+impl OptRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<OptRecord, DnsParseError> {
+        let udp_payload_size = u16::from(&rr.rrclass);
+        let e_rcode = ((rr.ttl >> 24) & 0xff) as u8;
+        let version = ((rr.ttl >> 16) & 0xff) as u8;
+        let opt_do = ((rr.ttl >> 15) & 1) as u8;
+        let z = (rr.ttl & 0x7fff) as u16;
+
+        let mut options = Vec::new();
+        let mut rest = &rr.rdata[..];
+        while !rest.is_empty() {
+            let (r, option) =
+                parse_edns_option(rest).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+            options.push(option);
+            rest = r;
+        }
+
+        Ok(OptRecord {
+            udp_payload_size,
+            e_rcode,
+            version,
+            opt_do,
+            z,
+            options,
+        })
+    }
+}
+
+impl OptRecord {
+    /// The extended rcode, version and flags as they are stored in the
+    /// TTL of the record.
+    pub fn ttl(&self) -> u32 {
+        (u32::from(self.e_rcode) << 24)
+            | (u32::from(self.version) << 16)
+            | (u32::from(self.opt_do & 1) << 15)
+            | u32::from(self.z & 0x7fff)
+    }
+}
+
+impl std::fmt::Display for OptRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<ROOT>")?;
+        for option in self.options.iter() {
+            write!(f, " {}", option)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edns_option_parser() {
+        let rdata: [u8; 12] = [
+            0x00, 0x0a, 0x00, 0x08, 0x8e, 0xa0, 0xf3, 0xd3, 0x6b, 0x19, 0x5c, 0xf7,
+        ];
+
+        let (r, edns_option) = parse_edns_option(&rdata).unwrap();
+        println!("{:?}", edns_option);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn test_opt_record_parser() {
+        let rdata: [u8; 23] = [
+            0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0a, 0x00,
+            0x08, 0x8e, 0xa0, 0xf3, 0xd3, 0x6b, 0x19, 0x5c, 0xf7,
+        ];
+
+        let (_, rr) = parse_resource_record(&rdata, &rdata).unwrap();
+        assert_eq!(rr.rrtype, DnsType::OPT);
+
+        let v = OptRecord::new(&rr, &rdata).unwrap();
+        println!("{:#?}", v);
+        assert_eq!(v.udp_payload_size, 4096);
+    }
+
+    fn option(code: u16, data: &[u8]) -> EdnsOption {
+        let mut wire = code.to_be_bytes().to_vec();
+        wire.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        wire.extend_from_slice(data);
+        let (_, option) = parse_edns_option(&wire).unwrap();
+        assert_eq!(data, &option.to_wire()[..]);
+        option
+    }
+
+    #[test]
+    fn test_edns_options() {
+        assert_eq!("NSID=6e7331 (\"ns1\")", option(3, b"ns1").to_string());
+        assert_eq!("DAU=8,13,15", option(5, &[8, 13, 15]).to_string());
+        let ecs = option(8, &[0, 1, 24, 0, 192, 0, 2]);
+        assert_eq!("ECS=192.0.2.0/24/0", ecs.to_string());
+        assert_eq!(
+            r#"{"ECS":{"family":1,"source_prefix":24,"scope_prefix":0,"address":"192.0.2.0"}}"#,
+            serde_json::to_string(&ecs).unwrap()
+        );
+        assert_eq!(
+            "ECS=2001:db8::/32/0",
+            option(8, &[0, 2, 32, 0, 32, 1, 13, 184]).to_string()
+        );
+        assert_eq!("EXPIRE", option(9, &[]).to_string());
+        assert_eq!("EXPIRE=3600", option(9, &[0, 0, 14, 16]).to_string());
+        assert_eq!("KEEPALIVE=30.0s", option(11, &[1, 44]).to_string());
+        assert_eq!("PADDING=3", option(12, &[0, 0, 0]).to_string());
+        assert_eq!("CHAIN=example", option(13, b"\x07example\x00").to_string());
+        assert_eq!(
+            "KEY-TAG=20326,38696",
+            option(14, &[79, 102, 151, 40]).to_string()
+        );
+        let ede = option(15, b"\x00\x12blocked by policy");
+        assert_eq!(
+            "EDE=18 (Prohibited): \"blocked by policy\"",
+            ede.to_string()
+        );
+        assert_eq!(
+            r#"{"EDE":{"info_code":18,"purpose":"Prohibited","extra_text":"blocked by policy"}}"#,
+            serde_json::to_string(&ede).unwrap()
+        );
+        assert_eq!(
+            "REPORT-CHANNEL=a.b",
+            option(18, b"\x01a\x01b\x00").to_string()
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_invalid_options_keep_data() {
+        let unknown = option(65001, &[0xde, 0xad]);
+        assert_eq!("OPT65001=dead", unknown.to_string());
+        assert_eq!(
+            r#"{"Unknown":{"code":65001,"data":"dead"}}"#,
+            serde_json::to_string(&unknown).unwrap()
+        );
+        let ecs = option(8, &[0, 1, 24, 0, 192]);
+        assert_eq!("OPT8=00011800c0", ecs.to_string());
+    }
+
+    #[test]
+    fn test_opt_record_has_no_option_limit() {
+        let mut data = vec![0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 100];
+        data.extend_from_slice(&[0, 12, 0, 0].repeat(25));
+        let (_, rr) = parse_resource_record(&data, &data).unwrap();
+        let v = OptRecord::new(&rr, &data).unwrap();
+        assert_eq!(25, v.options.len());
+    }
+}
@@ -0,0 +1,641 @@
+use crate::parse::dns::*;
+use std::collections::HashMap;
+
+/// Writes dns messages in wire format.
+///
+/// With compression, a name that ends like an earlier one points to it
+/// (RFC 1035 4.1.4). Names in rdata are only compressed for the RFC 1035
+/// record types, as RFC 3597 requires.
+pub(crate) struct DnsEncoder {
+    buf: Vec<u8>,
+    compress: bool,
+    names: HashMap<Vec<u8>, u16>,
+}
+
+impl DnsEncoder {
+    pub fn new(compress: bool) -> DnsEncoder {
+        DnsEncoder {
+            buf: Vec::new(),
+            compress,
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn put_u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn put_bytes(&mut self, v: &[u8]) {
+        self.buf.extend_from_slice(v);
+    }
+
+    /// Write `name`, pointing to an earlier copy of its longest known
+    /// suffix when `compress` is allowed.
+    ///
+    /// Suffixes are matched on their exact bytes, so case is kept.
+    fn put_name(&mut self, name: &DomainName, compress: bool) {
+        let compress = compress && self.compress;
+        let labels: Vec<&[u8]> = name.labels().collect();
+        for (i, label) in labels.iter().enumerate() {
+            if compress {
+                let suffix = DomainName::from_decoded(&labels[i..]).to_wire();
+                if let Some(&offset) = self.names.get(&suffix) {
+                    self.put_u16(0xc000 | offset);
+                    return;
+                }
+                if self.buf.len() < 0x4000 {
+                    self.names.insert(suffix, self.buf.len() as u16);
+                }
+            }
+            self.put_u8(label.len() as u8);
+            self.put_bytes(label);
+        }
+        self.put_u8(0);
+    }
+
+    /// Write the message, the section counts follow the record lists.
+    ///
+    /// The upper 8 bits of an extended rcode go in the TTL of the OPT
+    /// record (RFC 6891 6.1.3), so they are lost in messages without one.
+    pub fn put_message(&mut self, dns_message: &DnsMessage) {
+        let header = &dns_message.header;
+        let upper_rcode = u32::from(u16::from(&header.flags.rcode) >> 4);
+        self.put_u16(header.id);
+        self.put_u16(u16::from(&header.flags));
+        self.put_u16(dns_message.queries.len() as u16);
+        self.put_u16(dns_message.answers.len() as u16);
+        self.put_u16(dns_message.nsrecords.len() as u16);
+        self.put_u16(dns_message.arecords.len() as u16);
+        for query in dns_message.queries.iter() {
+            self.put_query(query);
+        }
+        let sections = [
+            &dns_message.answers,
+            &dns_message.nsrecords,
+            &dns_message.arecords,
+        ];
+        for records in sections {
+            for rr in records.iter() {
+                match rr.rrtype {
+                    DnsType::OPT => {
+                        self.put_record_ttl(rr, (rr.ttl & 0x00ff_ffff) | (upper_rcode << 24))
+                    }
+                    _ => self.put_record(rr),
+                }
+            }
+        }
+    }
+
+    pub fn put_query(&mut self, query: &Query) {
+        self.put_name(&query.name, true);
+        self.put_u16(u16::from(&query.qtype));
+        self.put_u16(u16::from(&query.qclass) | if query.unicast_response { 0x8000 } else { 0 });
+    }
+
+    /// Write a resource record.
+    ///
+    /// Rdata with names is written from the decoded record, since pointers
+    /// in the original rdata only make sense in the original message. Other
+    /// rdata is copied as it is.
+    pub fn put_record(&mut self, rr: &ResourceRecord) {
+        self.put_record_ttl(rr, rr.ttl);
+    }
+
+    fn put_record_ttl(&mut self, rr: &ResourceRecord, ttl: u32) {
+        self.put_name(&rr.name, true);
+        self.put_u16(u16::from(&rr.rrtype));
+        self.put_u16(u16::from(&rr.rrclass) | if rr.cache_flush { 0x8000 } else { 0 });
+        self.put_u32(ttl);
+        let start = self.buf.len();
+        self.put_u16(0);
+        match &rr.record {
+            Some(
+                record @ (RRecordTypes::MX(_)
+                | RRecordTypes::PTR(_)
+                | RRecordTypes::CNAME(_)
+                | RRecordTypes::NS(_)
+                | RRecordTypes::SRV(_)
+                | RRecordTypes::SOA(_)),
+            ) => self.put_rdata(record),
+            _ => self.put_bytes(&rr.rdata),
+        }
+        let rdlength = (self.buf.len() - start - 2) as u16;
+        self.buf[start..start + 2].copy_from_slice(&rdlength.to_be_bytes());
+    }
+
+    /// Write the rdata of a decoded record.
+    pub fn put_rdata(&mut self, record: &RRecordTypes) {
+        match record {
+            RRecordTypes::MX(v) => {
+                self.put_u16(v.preference);
+                self.put_name(&v.exchange, true);
+            }
+            RRecordTypes::A(v) => self.put_bytes(&v.address.octets()),
+            RRecordTypes::AAAA(v) => self.put_bytes(&v.address.octets()),
+            RRecordTypes::PTR(v) => self.put_name(&v.name, true),
+            RRecordTypes::TXT(v) => {
+                for string in v.strings.iter() {
+                    self.put_u8(string.len() as u8);
+                    self.put_bytes(string);
+                }
+            }
+            RRecordTypes::CNAME(v) => self.put_name(&v.name, true),
+            RRecordTypes::NS(v) => self.put_name(&v.name, true),
+            RRecordTypes::SRV(v) => {
+                self.put_u16(v.prio);
+                self.put_u16(v.weight);
+                self.put_u16(v.port);
+                self.put_name(&v.target, false);
+            }
+            RRecordTypes::SOA(v) => {
+                self.put_name(&v.mname, true);
+                self.put_name(&v.rname, true);
+                self.put_u32(v.serial);
+                self.put_u32(v.refresh);
+                self.put_u32(v.retry);
+                self.put_u32(v.expire);
+                self.put_u32(v.minimum);
+            }
+            RRecordTypes::OPT(v) => {
+                for option in v.options.iter() {
+                    let data = option.to_wire();
+                    self.put_u16(option.code());
+                    self.put_u16(data.len() as u16);
+                    self.put_bytes(&data);
+                }
+            }
+            RRecordTypes::DNSKEY(v) => {
+                self.put_u16(v.flags);
+                self.put_u8(v.protocol);
+                self.put_u8(v.algorithm);
+                self.put_bytes(&v.public_key);
+            }
+            RRecordTypes::RRSIG(v) => {
+                self.put_u16(u16::from(&v.type_covered));
+                self.put_u8(v.algorithm);
+                self.put_u8(v.labels);
+                self.put_u32(v.original_ttl);
+                self.put_u32(v.expiration);
+                self.put_u32(v.inception);
+                self.put_u16(v.key_tag);
+                self.put_name(&v.signer_name, false);
+                self.put_bytes(&v.signature);
+            }
+            RRecordTypes::DS(v) => {
+                self.put_u16(v.key_tag);
+                self.put_u8(v.algorithm);
+                self.put_u8(v.digest_type);
+                self.put_bytes(&v.digest);
+            }
+            RRecordTypes::NSEC(v) => {
+                self.put_name(&v.next_domain_name, false);
+                self.put_bytes(&encode_type_bitmaps(&v.types));
+            }
+            RRecordTypes::NSEC3(v) => {
+                self.put_u8(v.hash_algorithm);
+                self.put_u8(v.flags);
+                self.put_u16(v.iterations);
+                self.put_u8(v.salt.len() as u8);
+                self.put_bytes(&v.salt);
+                self.put_u8(v.next_hashed_owner.len() as u8);
+                self.put_bytes(&v.next_hashed_owner);
+                self.put_bytes(&encode_type_bitmaps(&v.types));
+            }
+            RRecordTypes::NSEC3PARAM(v) => {
+                self.put_u8(v.hash_algorithm);
+                self.put_u8(v.flags);
+                self.put_u16(v.iterations);
+                self.put_u8(v.salt.len() as u8);
+                self.put_bytes(&v.salt);
+            }
+            RRecordTypes::SVCB(v) | RRecordTypes::HTTPS(v) => {
+                self.put_u16(v.priority);
+                self.put_name(&v.target, false);
+                for param in v.params.iter() {
+                    let value = param.to_wire();
+                    self.put_u16(param.key().0);
+                    self.put_u16(value.len() as u16);
+                    self.put_bytes(&value);
+                }
+            }
+            RRecordTypes::CAA(v) => {
+                self.put_u8(v.flags);
+                self.put_u8(v.tag.len() as u8);
+                self.put_bytes(v.tag.as_bytes());
+                self.put_bytes(&v.value);
+            }
+            RRecordTypes::TLSA(v) | RRecordTypes::SMIMEA(v) => {
+                self.put_u8(v.usage);
+                self.put_u8(v.selector);
+                self.put_u8(v.matching_type);
+                self.put_bytes(&v.data);
+            }
+            RRecordTypes::SSHFP(v) => {
+                self.put_u8(v.algorithm);
+                self.put_u8(v.fingerprint_type);
+                self.put_bytes(&v.fingerprint);
+            }
+            RRecordTypes::OPENPGPKEY(v) => self.put_bytes(&v.public_key),
+            RRecordTypes::CERT(v) => {
+                self.put_u16(v.cert_type);
+                self.put_u16(v.key_tag);
+                self.put_u8(v.algorithm);
+                self.put_bytes(&v.certificate);
+            }
+            RRecordTypes::NAPTR(v) => {
+                self.put_u16(v.order);
+                self.put_u16(v.preference);
+                for string in [&v.flags, &v.services, &v.regexp] {
+                    self.put_u8(string.len() as u8);
+                    self.put_bytes(string);
+                }
+                self.put_name(&v.replacement, false);
+            }
+            RRecordTypes::URI(v) => {
+                self.put_u16(v.priority);
+                self.put_u16(v.weight);
+                self.put_bytes(&v.target);
+            }
+            RRecordTypes::LOC(v) => {
+                self.put_u8(v.version);
+                self.put_u8(v.size);
+                self.put_u8(v.horiz_pre);
+                self.put_u8(v.vert_pre);
+                self.put_u32(v.latitude);
+                self.put_u32(v.longitude);
+                self.put_u32(v.altitude);
+            }
+            RRecordTypes::HINFO(v) => {
+                for string in [&v.cpu, &v.os] {
+                    self.put_u8(string.len() as u8);
+                    self.put_bytes(string);
+                }
+            }
+            RRecordTypes::RP(v) => {
+                self.put_name(&v.mbox, false);
+                self.put_name(&v.txt, false);
+            }
+            RRecordTypes::AFSDB(v) => {
+                self.put_u16(v.subtype);
+                self.put_name(&v.hostname, false);
+            }
+            RRecordTypes::DNAME(v) => self.put_name(&v.target, false),
+            RRecordTypes::NULL(v) => self.put_bytes(&v.data),
+            RRecordTypes::NB(v) => self.put_bytes(&v.to_wire()),
+            RRecordTypes::NBSTAT(v) => self.put_bytes(&v.to_wire()),
+            RRecordTypes::Raw(v) => self.put_bytes(&v.rdata),
+            RRecordTypes::Malformed(v) => self.put_bytes(&v.raw.rdata),
+        }
+    }
+}
+
+/// The uncompressed wire rdata of `record`.
+pub(crate) fn encode_rdata(record: &RRecordTypes) -> Vec<u8> {
+    let mut encoder = DnsEncoder::new(false);
+    encoder.put_rdata(record);
+    encoder.finish()
+}
+
+impl DnsMessage<'_> {
+    /// Encode the message in wire format, with or without name compression.
+    ///
+    /// The section counts are taken from the record lists, not the header.
+    pub fn encode(&self, compress: bool) -> Vec<u8> {
+        let mut encoder = DnsEncoder::new(compress);
+        encoder.put_message(self);
+        encoder.finish()
+    }
+}
+
+/// Builds a `DnsMessage` from scratch, e.g. to synthesize test data.
+#[derive(Clone, Debug, Default)]
+pub struct DnsMessageBuilder {
+    id: u16,
+    flags: Flags,
+    queries: Vec<Query>,
+    answers: Vec<ResourceRecord<'static>>,
+    nsrecords: Vec<ResourceRecord<'static>>,
+    arecords: Vec<ResourceRecord<'static>>,
+}
+
+impl DnsMessageBuilder {
+    pub fn new(id: u16) -> DnsMessageBuilder {
+        DnsMessageBuilder {
+            id,
+            ..Default::default()
+        }
+    }
+
+    pub fn flags(mut self, flags: Flags) -> DnsMessageBuilder {
+        self.flags = flags;
+        self
+    }
+
+    pub fn query(
+        mut self,
+        name: DomainName,
+        qtype: DnsType,
+        qclass: DnsClass,
+    ) -> DnsMessageBuilder {
+        self.queries.push(Query {
+            name,
+            qtype,
+            qclass,
+            unicast_response: false,
+        });
+        self
+    }
+
+    pub fn answer(mut self, rr: ResourceRecord<'static>) -> DnsMessageBuilder {
+        self.answers.push(rr);
+        self
+    }
+
+    pub fn authority(mut self, rr: ResourceRecord<'static>) -> DnsMessageBuilder {
+        self.nsrecords.push(rr);
+        self
+    }
+
+    pub fn additional(mut self, rr: ResourceRecord<'static>) -> DnsMessageBuilder {
+        self.arecords.push(rr);
+        self
+    }
+
+    /// The message, with header counts matching the records added. OPT
+    /// records get the upper bits of the rcode, as they are encoded.
+    pub fn build(mut self) -> OwnedDnsMessage {
+        let e_rcode = (u16::from(&self.flags.rcode) >> 4) as u8;
+        for rr in self.arecords.iter_mut() {
+            if let Some(RRecordTypes::OPT(opt)) = &mut rr.record {
+                opt.e_rcode = e_rcode;
+                rr.ttl = opt.ttl();
+            }
+        }
+        DnsMessage {
+            header: DnsHeader {
+                id: self.id,
+                flags: self.flags,
+                qdcount: self.queries.len() as u16,
+                ancount: self.answers.len() as u16,
+                nscount: self.nsrecords.len() as u16,
+                arcount: self.arecords.len() as u16,
+            },
+            queries: self.queries,
+            answers: self.answers,
+            nsrecords: self.nsrecords,
+            arecords: self.arecords,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const DNS_RESPONSE: &[u8] = include_bytes!("../../../fixtures/dns/dns_response1.bin");
+
+    fn name(s: &str) -> DomainName {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_encode_fixture() {
+        let (_rest, parsed) = dns_message(DNS_RESPONSE, true).unwrap();
+        assert_eq!(DNS_RESPONSE, &parsed.encode(true)[..]);
+
+        let uncompressed = parsed.encode(false);
+        assert!(uncompressed.len() > DNS_RESPONSE.len());
+        let (_rest, reparsed) = dns_message(&uncompressed, true).unwrap();
+        assert_eq!(parsed, reparsed);
+    }
+
+    #[test]
+    fn test_compression_keeps_case() {
+        let a = |owner: &str| {
+            let record = RRecordTypes::A(ARecord {
+                address: Ipv4Addr::new(192, 0, 2, 1),
+            });
+            ResourceRecord::new(name(owner), DnsClass::IN, 60, record)
+        };
+        let msg = DnsMessageBuilder::new(1)
+            .answer(a("www.Example.com"))
+            .answer(a("www.example.com"))
+            .answer(a("mail.example.com"))
+            .build();
+        let wire = msg.encode(true);
+        assert_eq!(
+            &b"\x04mail\xc0\x2f"[..],
+            &wire[wire.len() - 21..wire.len() - 14]
+        );
+
+        let (_rest, parsed) = dns_message(&wire, true).unwrap();
+        let names: Vec<_> = parsed
+            .answers
+            .iter()
+            .map(|rr| rr.name.to_string())
+            .collect();
+        assert_eq!(
+            vec!["www.Example.com", "www.example.com", "mail.example.com"],
+            names
+        );
+    }
+
+    /// xorshift64, enough to vary the generated messages.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn random_name(rng: &mut Rng) -> DomainName {
+        let pool: [&[u8]; 6] = [
+            b"www",
+            b"Example",
+            b"example",
+            b"com",
+            b"_tcp",
+            b"\x00.\xff",
+        ];
+        let labels: Vec<&[u8]> = (0..rng.below(4)).map(|_| pool[rng.below(6)]).collect();
+        DomainName::from_labels(labels).unwrap()
+    }
+
+    fn random_record(rng: &mut Rng) -> ResourceRecord<'static> {
+        let record = match rng.below(17) {
+            0 => RRecordTypes::A(ARecord {
+                address: Ipv4Addr::from(rng.below(1 << 31) as u32),
+            }),
+            1 => RRecordTypes::AAAA(AAAARecord {
+                address: Ipv6Addr::from(rng.below(1 << 31) as u128),
+            }),
+            2 => RRecordTypes::MX(MxRecord {
+                preference: rng.below(100) as u16,
+                exchange: random_name(rng),
+            }),
+            3 => RRecordTypes::PTR(PtrRecord {
+                name: random_name(rng),
+            }),
+            4 => RRecordTypes::CNAME(CNameRecord {
+                name: random_name(rng),
+            }),
+            5 => RRecordTypes::NS(NsRecord {
+                name: random_name(rng),
+            }),
+            6 => RRecordTypes::SRV(SrvRecord {
+                prio: 10,
+                weight: rng.below(100) as u16,
+                port: 443,
+                target: random_name(rng),
+            }),
+            7 => RRecordTypes::SOA(SoaRecord {
+                mname: random_name(rng),
+                rname: random_name(rng),
+                serial: rng.below(1 << 31) as u32,
+                refresh: 3600,
+                retry: 600,
+                expire: 86400,
+                minimum: 60,
+            }),
+            8 => RRecordTypes::TXT(TxtRecord::from_strings(
+                (0..1 + rng.below(3))
+                    .map(|_| vec![b'x'; rng.below(256)])
+                    .collect(),
+            )),
+            9 => RRecordTypes::NSEC(NsecRecord {
+                next_domain_name: random_name(rng),
+                types: vec![
+                    DnsType::A,
+                    DnsType::RRSIG,
+                    DnsType::from(256 + rng.below(0xff00) as u16),
+                ],
+            }),
+            10 => RRecordTypes::RRSIG(RrsigRecord {
+                type_covered: DnsType::AAAA,
+                algorithm: 13,
+                labels: 2,
+                original_ttl: 300,
+                expiration: rng.below(1 << 31) as u32,
+                inception: 1_700_000_000,
+                key_tag: rng.below(0x10000) as u16,
+                signer_name: random_name(rng),
+                signature: vec![0x5a; rng.below(64)],
+            }),
+            11 => RRecordTypes::HTTPS(SvcbRecord {
+                priority: 1,
+                target: random_name(rng),
+                params: vec![
+                    SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+                    SvcParam::Port(rng.below(0x10000) as u16),
+                    SvcParam::Ipv4hint(vec![Ipv4Addr::from(rng.below(1 << 31) as u32)]),
+                ],
+            }),
+            12 => RRecordTypes::CAA(CaaRecord {
+                flags: 0,
+                tag: String::from("issue"),
+                value: b"letsencrypt.org".to_vec(),
+            }),
+            13 => RRecordTypes::TLSA(TlsaRecord {
+                usage: 3,
+                selector: 1,
+                matching_type: 1,
+                data: vec![0x5a; 32],
+            }),
+            14 => RRecordTypes::NAPTR(NaptrRecord {
+                order: 100,
+                preference: rng.below(100) as u16,
+                flags: b"S".to_vec(),
+                services: b"SIP+D2U".to_vec(),
+                regexp: Vec::new(),
+                replacement: random_name(rng),
+            }),
+            15 => RRecordTypes::RP(RpRecord {
+                mbox: random_name(rng),
+                txt: random_name(rng),
+            }),
+            _ => {
+                let rdata = vec![0xab; rng.below(8)];
+                RRecordTypes::Raw(RawRecord {
+                    rrtype: 0xff00 + rng.below(0xff) as u16,
+                    rdlength: rdata.len() as u16,
+                    rdata,
+                })
+            }
+        };
+        ResourceRecord::new(
+            random_name(rng),
+            DnsClass::IN,
+            rng.below(86400) as u32,
+            record,
+        )
+    }
+
+    fn random_opt(rng: &mut Rng) -> ResourceRecord<'static> {
+        let opt = OptRecord {
+            udp_payload_size: 512 + rng.below(4096) as u16,
+            e_rcode: 0,
+            version: 0,
+            opt_do: rng.below(2) as u8,
+            z: 0,
+            options: vec![EdnsOption::new(10, &[0x5a; 8])],
+        };
+        ResourceRecord::new(DomainName::root(), DnsClass::IN, 0, RRecordTypes::OPT(opt))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            // Extended rcodes need an OPT record.
+            let edns = rng.below(2) == 1;
+            let rcodes = [Rcode::NXDomain, Rcode::BadVers, Rcode::BadCookie];
+            let flags = Flags {
+                qr: Qr::Response,
+                aa: rng.below(2) == 1,
+                rd: true,
+                rcode: rcodes[if edns { rng.below(3) } else { 0 }].clone(),
+                ..Default::default()
+            };
+            let mut builder = DnsMessageBuilder::new(rng.below(0x10000) as u16)
+                .flags(flags)
+                .query(random_name(&mut rng), DnsType::ALL, DnsClass::IN);
+            for _ in 0..rng.below(5) {
+                builder = builder.answer(random_record(&mut rng));
+            }
+            for _ in 0..rng.below(3) {
+                builder = builder.authority(random_record(&mut rng));
+            }
+            for _ in 0..rng.below(3) {
+                builder = builder.additional(random_record(&mut rng));
+            }
+            if edns {
+                builder = builder.additional(random_opt(&mut rng));
+            }
+            let msg = builder.build();
+
+            for compress in [false, true] {
+                let wire = msg.encode(compress);
+                let (rest, parsed) = dns_message(&wire, true).unwrap();
+                assert!(rest.is_empty());
+                assert_eq!(msg, parsed);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::parse::dns::*;
+use nom::{Context, Err, ErrorKind};
+use serde_derive::Serialize;
+
+/// Message section a parse error was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[rustfmt::skip]
+pub enum Section {
+    Header, Question, Answer, Authority, Additional,
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Section::Header => "header",
+            Section::Question => "question",
+            Section::Answer => "answer",
+            Section::Authority => "authority",
+            Section::Additional => "additional",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Error returned when a dns message can not be decoded.
+///
+/// `offset` is the position in the full dns message where the problem was
+/// found, `section` and `index` tell which record was being parsed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DnsParseError {
+    /// The message ended before the item being parsed was complete.
+    Truncated {
+        offset: usize,
+        section: Section,
+        index: usize,
+    },
+    /// A domain name could not be decoded.
+    InvalidName {
+        offset: usize,
+        section: Section,
+        index: usize,
+        reason: NameError,
+    },
+    /// The rdata does not match the layout of its record type.
+    InvalidRdata {
+        offset: usize,
+        section: Section,
+        index: usize,
+        rrtype: DnsType,
+    },
+}
+
+impl DnsParseError {
+    pub(crate) fn truncated(offset: usize) -> DnsParseError {
+        DnsParseError::Truncated {
+            offset,
+            section: Section::Header,
+            index: 0,
+        }
+    }
+
+    pub(crate) fn invalid_name(offset: usize, reason: NameError) -> DnsParseError {
+        DnsParseError::InvalidName {
+            offset,
+            section: Section::Header,
+            index: 0,
+            reason,
+        }
+    }
+
+    pub(crate) fn invalid_rdata(offset: usize, rrtype: &DnsType) -> DnsParseError {
+        DnsParseError::InvalidRdata {
+            offset,
+            section: Section::Header,
+            index: 0,
+            rrtype: rrtype.clone(),
+        }
+    }
+
+    /// Map a nom error from parsing the item starting at `offset` of `data`.
+    pub(crate) fn from_nom(err: Err<&[u8]>, data: &[u8], offset: usize) -> DnsParseError {
+        match err {
+            Err::Error(Context::Code(i, ErrorKind::Custom(len)))
+            | Err::Failure(Context::Code(i, ErrorKind::Custom(len))) => {
+                let offset = offset_in(data, i).map_or(offset, |o| o.saturating_sub(1));
+                DnsParseError::invalid_name(offset, NameError::LabelTooLong(len as u8))
+            }
+            _ => DnsParseError::truncated(offset),
+        }
+    }
+
+    /// Set the section and record index the error belongs to.
+    pub(crate) fn at(mut self, in_section: Section, at_index: usize) -> DnsParseError {
+        match &mut self {
+            DnsParseError::Truncated { section, index, .. }
+            | DnsParseError::InvalidName { section, index, .. }
+            | DnsParseError::InvalidRdata { section, index, .. } => {
+                *section = in_section;
+                *index = at_index;
+            }
+        }
+        self
+    }
+
+    pub fn offset(&self) -> usize {
+        match self {
+            DnsParseError::Truncated { offset, .. }
+            | DnsParseError::InvalidName { offset, .. }
+            | DnsParseError::InvalidRdata { offset, .. } => *offset,
+        }
+    }
+
+    pub fn section(&self) -> Section {
+        match self {
+            DnsParseError::Truncated { section, .. }
+            | DnsParseError::InvalidName { section, .. }
+            | DnsParseError::InvalidRdata { section, .. } => *section,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            DnsParseError::Truncated { index, .. }
+            | DnsParseError::InvalidName { index, .. }
+            | DnsParseError::InvalidRdata { index, .. } => *index,
+        }
+    }
+}
+
+impl std::fmt::Display for DnsParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DnsParseError::Truncated { .. } => write!(f, "truncated message")?,
+            DnsParseError::InvalidName { reason, .. } => write!(f, "invalid name, {}", reason)?,
+            DnsParseError::InvalidRdata { rrtype, .. } => write!(f, "invalid {} rdata", rrtype)?,
+        }
+        write!(
+            f,
+            " at offset {} ({} #{})",
+            self.offset(),
+            self.section(),
+            self.index()
+        )
+    }
+}
+
+impl std::error::Error for DnsParseError {}
+
+/// Remaining input and the parsed value, or where parsing failed.
+pub(crate) type ParseResult<'a, T> = Result<(&'a [u8], T), DnsParseError>;
+
+/// Position of `part` within `full`, if `part` is a slice of `full`.
+pub(crate) fn offset_in(full: &[u8], part: &[u8]) -> Option<usize> {
+    (part.as_ptr() as usize)
+        .checked_sub(full.as_ptr() as usize)
+        .filter(|offs| offs + part.len() <= full.len())
+}
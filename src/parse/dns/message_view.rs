@@ -0,0 +1,424 @@
+use crate::parse::dns::*;
+use std::borrow::Cow;
+
+/// How much of a section `DnsMessageView::decode` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionDecode {
+    /// Leave the section out.
+    Skip,
+    /// Decode the records but not their rdata.
+    Parse,
+    /// Decode the records and their rdata.
+    Resolve,
+}
+
+/// Which sections and record types `DnsMessageView::decode` resolves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodePolicy {
+    pub answers: SectionDecode,
+    pub authority: SectionDecode,
+    pub additional: SectionDecode,
+    /// Only resolve the rdata of these types, all types if `None`.
+    pub rrtypes: Option<Vec<DnsType>>,
+}
+
+impl Default for DecodePolicy {
+    /// Resolve everything, like `dns_message(data, true)`.
+    fn default() -> DecodePolicy {
+        DecodePolicy {
+            answers: SectionDecode::Resolve,
+            authority: SectionDecode::Resolve,
+            additional: SectionDecode::Resolve,
+            rrtypes: None,
+        }
+    }
+}
+
+impl DecodePolicy {
+    /// Header and questions only.
+    pub fn questions_only() -> DecodePolicy {
+        DecodePolicy {
+            answers: SectionDecode::Skip,
+            authority: SectionDecode::Skip,
+            additional: SectionDecode::Skip,
+            rrtypes: None,
+        }
+    }
+
+    fn resolves(&self, rrtype: &DnsType) -> bool {
+        self.rrtypes.as_ref().map_or(true, |t| t.contains(rrtype))
+    }
+}
+
+/// A question as found in the message. The name is decoded on demand.
+#[derive(Clone, Debug)]
+pub struct QuestionView<'a> {
+    data: &'a [u8],
+    name_chain: CompressedNameChain<'a>,
+    index: usize,
+    pub qtype: DnsType,
+    pub qclass: DnsClass,
+    pub unicast_response: bool,
+}
+
+impl<'a> QuestionView<'a> {
+    pub fn name(&self) -> Result<DomainName, DnsParseError> {
+        self.name_chain
+            .resolve_name(self.data)
+            .map_err(|e| e.at(Section::Question, self.index))
+    }
+
+    pub fn to_query(&self) -> Result<Query, DnsParseError> {
+        Ok(Query {
+            name: self.name()?,
+            qtype: self.qtype.clone(),
+            qclass: self.qclass.clone(),
+            unicast_response: self.unicast_response,
+        })
+    }
+}
+
+/// A resource record as found in the message. Name and rdata are decoded
+/// on demand.
+#[derive(Clone, Debug)]
+pub struct RecordView<'a> {
+    data: &'a [u8],
+    name_chain: CompressedNameChain<'a>,
+    section: Section,
+    index: usize,
+    pub rrtype: DnsType,
+    pub rrclass: DnsClass,
+    pub cache_flush: bool,
+    pub ttl: u32,
+    pub rdata: &'a [u8],
+}
+
+impl<'a> RecordView<'a> {
+    pub fn name(&self) -> Result<DomainName, DnsParseError> {
+        self.name_chain
+            .resolve_name(self.data)
+            .map_err(|e| e.at(self.section, self.index))
+    }
+
+    /// The record with its rdata left undecoded.
+    pub fn to_record(&self) -> Result<ResourceRecord<'a>, DnsParseError> {
+        Ok(ResourceRecord {
+            name: self.name()?,
+            rrtype: self.rrtype.clone(),
+            rrclass: self.rrclass.clone(),
+            cache_flush: self.cache_flush,
+            ttl: self.ttl,
+            rdlength: self.rdata.len() as u16,
+            rdata: Cow::Borrowed(self.rdata),
+            record: None,
+        })
+    }
+
+    /// The record with its rdata decoded.
+    pub fn resolve(&self) -> Result<ResourceRecord<'a>, DnsParseError> {
+        let mut rr = self.to_record()?;
+        rr.resolve_at(self.data, self.section, self.index);
+        Ok(rr)
+    }
+}
+
+fn scan_question<'a>(
+    data: &'a [u8],
+    i: &'a [u8],
+    _section: Section,
+    index: usize,
+) -> ParseResult<'a, QuestionView<'a>> {
+    let offset = offset_in(data, i).unwrap_or(0);
+    let (rest, (name_chain, qtype, qclass)) =
+        parse_query_fields(i).map_err(|e| DnsParseError::from_nom(e, data, offset))?;
+    let (qclass, unicast_response) = qclass.split_mdns_bit();
+    Ok((
+        rest,
+        QuestionView {
+            data,
+            name_chain,
+            index,
+            qtype,
+            qclass,
+            unicast_response,
+        },
+    ))
+}
+
+fn scan_record<'a>(
+    data: &'a [u8],
+    i: &'a [u8],
+    section: Section,
+    index: usize,
+) -> ParseResult<'a, RecordView<'a>> {
+    let offset = offset_in(data, i).unwrap_or(0);
+    let (rest, (name_chain, rrtype, rrclass, ttl, rdata)) =
+        parse_resource_record_fields(i).map_err(|e| DnsParseError::from_nom(e, data, offset))?;
+    let (rrclass, cache_flush) = split_record_class(&rrtype, rrclass);
+    Ok((
+        rest,
+        RecordView {
+            data,
+            name_chain,
+            section,
+            index,
+            rrtype,
+            rrclass,
+            cache_flush,
+            ttl,
+            rdata,
+        },
+    ))
+}
+
+type ScanFn<'a, T> = fn(&'a [u8], &'a [u8], Section, usize) -> ParseResult<'a, T>;
+
+/// Iterates the entries of one section without allocating.
+///
+/// An error is returned once, after which the iterator is done.
+pub struct SectionIter<'a, T> {
+    data: &'a [u8],
+    rest: &'a [u8],
+    section: Section,
+    index: usize,
+    count: usize,
+    error: Option<DnsParseError>,
+    scan: ScanFn<'a, T>,
+}
+
+impl<'a, T> SectionIter<'a, T> {
+    fn new(
+        data: &'a [u8],
+        rest: Result<&'a [u8], DnsParseError>,
+        section: Section,
+        count: u16,
+        scan: ScanFn<'a, T>,
+    ) -> SectionIter<'a, T> {
+        let (rest, error) = match rest {
+            Ok(rest) => (rest, None),
+            Err(e) => (&data[data.len()..], Some(e)),
+        };
+        SectionIter {
+            data,
+            rest,
+            section,
+            index: 0,
+            count: count as usize,
+            error,
+            scan,
+        }
+    }
+}
+
+impl<'a, T> Iterator for SectionIter<'a, T> {
+    type Item = Result<T, DnsParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            self.index = self.count;
+            return Some(Err(e));
+        }
+        if self.index >= self.count {
+            return None;
+        }
+        match (self.scan)(self.data, self.rest, self.section, self.index) {
+            Ok((rest, item)) => {
+                self.rest = rest;
+                self.index += 1;
+                Some(Ok(item))
+            }
+            Err(e) => {
+                let e = e.at(self.section, self.index);
+                self.index = self.count;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.count - self.index + self.error.is_some() as usize;
+        (left.min(1), Some(left))
+    }
+}
+
+/// A dns message decoded on demand.
+///
+/// Only the header is parsed up front. The sections are walked in place
+/// when iterated, names and rdata are decoded only when asked for.
+#[derive(Clone, Debug)]
+pub struct DnsMessageView<'a> {
+    data: &'a [u8],
+    header: DnsHeader,
+}
+
+impl<'a> DnsMessageView<'a> {
+    pub fn new(data: &'a [u8]) -> Result<DnsMessageView<'a>, DnsParseError> {
+        let (_, header) =
+            parse_dns_header(data).map_err(|_| DnsParseError::truncated(data.len()))?;
+        Ok(DnsMessageView { data, header })
+    }
+
+    pub fn header(&self) -> &DnsHeader {
+        &self.header
+    }
+
+    pub fn questions(&self) -> SectionIter<'a, QuestionView<'a>> {
+        SectionIter::new(
+            self.data,
+            Ok(&self.data[12..]),
+            Section::Question,
+            self.header.qdcount,
+            scan_question,
+        )
+    }
+
+    pub fn answers(&self) -> SectionIter<'a, RecordView<'a>> {
+        self.records(Section::Answer, self.header.ancount)
+    }
+
+    pub fn authority(&self) -> SectionIter<'a, RecordView<'a>> {
+        self.records(Section::Authority, self.header.nscount)
+    }
+
+    pub fn additional(&self) -> SectionIter<'a, RecordView<'a>> {
+        self.records(Section::Additional, self.header.arcount)
+    }
+
+    fn records(&self, section: Section, count: u16) -> SectionIter<'a, RecordView<'a>> {
+        SectionIter::new(
+            self.data,
+            self.skip_to(section),
+            section,
+            count,
+            scan_record,
+        )
+    }
+
+    /// Walk the sections before `section` to find where it starts.
+    fn skip_to(&self, section: Section) -> Result<&'a [u8], DnsParseError> {
+        let mut questions = self.questions();
+        for question in questions.by_ref() {
+            question?;
+        }
+        let mut rest = questions.rest;
+        let sections = [
+            (Section::Answer, self.header.ancount),
+            (Section::Authority, self.header.nscount),
+        ];
+        for (s, count) in sections {
+            if s == section {
+                break;
+            }
+            let mut records = SectionIter::new(self.data, Ok(rest), s, count, scan_record);
+            for record in records.by_ref() {
+                record?;
+            }
+            rest = records.rest;
+        }
+        Ok(rest)
+    }
+
+    /// Build a `DnsMessage` doing only what `policy` asks for. Skipped
+    /// sections are left empty, the header counts are kept as they are.
+    pub fn decode(&self, policy: &DecodePolicy) -> Result<DnsMessage<'a>, DnsParseError> {
+        let queries = self
+            .questions()
+            .map(|q| q.and_then(|q| q.to_query()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let decode_section = |records: SectionIter<'a, RecordView<'a>>, how: SectionDecode| {
+            if how == SectionDecode::Skip {
+                return Ok(Vec::new());
+            }
+            records
+                .map(|rv| {
+                    let rv = rv?;
+                    if how == SectionDecode::Resolve && policy.resolves(&rv.rrtype) {
+                        rv.resolve()
+                    } else {
+                        rv.to_record()
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let mut dns_message = DnsMessage {
+            header: self.header.clone(),
+            queries,
+            answers: decode_section(self.answers(), policy.answers)?,
+            nsrecords: decode_section(self.authority(), policy.authority)?,
+            arecords: decode_section(self.additional(), policy.additional)?,
+        };
+        dns_message.apply_extended_rcode();
+        Ok(dns_message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DNS_RESPONSE: &[u8] = include_bytes!("../../../fixtures/dns/dns_response1.bin");
+
+    #[test]
+    fn test_question() {
+        let view = DnsMessageView::new(DNS_RESPONSE).unwrap();
+        assert_eq!(63343, view.header().id);
+        let questions: Vec<_> = view.questions().collect();
+        assert_eq!(1, questions.len());
+        let question = questions[0].as_ref().unwrap();
+        assert_eq!(DnsType::MX, question.qtype);
+        assert_eq!("google.com", question.name().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_sections() {
+        let view = DnsMessageView::new(DNS_RESPONSE).unwrap();
+        let answers: Vec<_> = view.answers().map(Result::unwrap).collect();
+        assert_eq!(6, answers.len());
+        assert!(answers.iter().all(|rv| rv.rrtype == DnsType::MX));
+        assert_eq!(0, view.authority().count());
+        let additional: Vec<_> = view.additional().map(Result::unwrap).collect();
+        assert_eq!(6, additional.len());
+        assert_eq!(
+            "smtp3.google.com",
+            additional[5].name().unwrap().to_string()
+        );
+        assert_eq!(&[216, 239, 37, 26][..], additional[0].rdata);
+    }
+
+    #[test]
+    fn test_decode_policy() {
+        let view = DnsMessageView::new(DNS_RESPONSE).unwrap();
+        let (_rest, full) = dns_message(DNS_RESPONSE, true).unwrap();
+        assert_eq!(full, view.decode(&DecodePolicy::default()).unwrap());
+
+        let msg = view.decode(&DecodePolicy::questions_only()).unwrap();
+        assert_eq!(full.queries, msg.queries);
+        assert_eq!(6, msg.header.ancount);
+        assert!(msg.answers.is_empty() && msg.arecords.is_empty());
+
+        let policy = DecodePolicy {
+            answers: SectionDecode::Parse,
+            rrtypes: Some(vec![DnsType::A]),
+            ..Default::default()
+        };
+        let msg = view.decode(&policy).unwrap();
+        assert!(msg.answers.iter().all(|rr| rr.record.is_none()));
+        assert!(msg.arecords.iter().all(|rr| rr.record.is_some()));
+    }
+
+    #[test]
+    fn test_truncated_section() {
+        let view = DnsMessageView::new(&DNS_RESPONSE[..40]).unwrap();
+        assert!(view.questions().all(|q| q.is_ok()));
+        let mut answers = view.answers();
+        let err = answers.next().unwrap().unwrap_err();
+        assert_eq!(Section::Answer, err.section());
+        assert_eq!(28, err.offset());
+        assert!(answers.next().is_none());
+
+        let err = view.additional().next().unwrap().unwrap_err();
+        assert_eq!(Section::Answer, err.section());
+        assert!(view.decode(&DecodePolicy::questions_only()).is_ok());
+        assert_eq!(Err(err), view.decode(&DecodePolicy::default()));
+    }
+}
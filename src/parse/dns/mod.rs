@@ -1,9 +1,27 @@
+pub mod error;
+pub use crate::parse::dns::error::*;
+
+pub mod domain_name;
+pub use crate::parse::dns::domain_name::*;
+
 pub mod compressed_name;
 pub use crate::parse::dns::compressed_name::*;
 
 pub mod resource_record;
 pub use crate::parse::dns::resource_record::*;
 
+pub mod edns;
+pub use crate::parse::dns::edns::*;
+
+pub mod dnssec;
+pub use crate::parse::dns::dnssec::*;
+
+pub mod svcb;
+pub use crate::parse::dns::svcb::*;
+
+pub mod txt;
+pub use crate::parse::dns::txt::*;
+
 pub mod dns_parameters;
 pub use crate::parse::dns::dns_parameters::*;
 
@@ -13,5 +31,29 @@ pub use crate::parse::dns::query::*;
 pub mod dns_message;
 pub use crate::parse::dns::dns_message::*;
 
+pub mod update;
+pub use crate::parse::dns::update::*;
+
+pub mod protocol;
+pub use crate::parse::dns::protocol::*;
+
+pub mod netbios;
+pub use crate::parse::dns::netbios::*;
+
+pub mod poisoning;
+pub use crate::parse::dns::poisoning::*;
+
+pub mod dnssd;
+pub use crate::parse::dns::dnssd::*;
+
+pub mod validate;
+pub use crate::parse::dns::validate::*;
+
+pub mod message_view;
+pub use crate::parse::dns::message_view::*;
+
+pub mod encoder;
+pub use crate::parse::dns::encoder::*;
+
 pub mod printer;
 pub use crate::parse::dns::printer::*;
@@ -0,0 +1,390 @@
+use crate::parse::dns::*;
+use serde::{Serialize, Serializer};
+use serde_derive::Serialize;
+use std::net::Ipv4Addr;
+
+/// NetBIOS general name service record (RFC 1002 4.2.1.3).
+pub const NBNS_NB: u16 = 0x20;
+/// NetBIOS node status record (RFC 1002 4.2.1.3).
+pub const NBNS_NBSTAT: u16 = 0x21;
+
+/// The record type mnemonic in a NBNS message.
+pub fn nbns_type_name(rrtype: &DnsType) -> String {
+    match u16::from(rrtype) {
+        NBNS_NB => String::from("NB"),
+        NBNS_NBSTAT => String::from("NBSTAT"),
+        _ => rrtype.to_string(),
+    }
+}
+
+/// The service registered under a NetBIOS name suffix.
+pub fn netbios_suffix_type(suffix: u8) -> Option<&'static str> {
+    let name = match suffix {
+        0x00 => "Workstation Service",
+        0x01 => "Messenger Service",
+        0x03 => "Messenger Service",
+        0x06 => "RAS Server Service",
+        0x1b => "Domain Master Browser",
+        0x1c => "Domain Controllers",
+        0x1d => "Master Browser",
+        0x1e => "Browser Service Elections",
+        0x1f => "NetDDE Service",
+        0x20 => "File Server Service",
+        0x21 => "RAS Client Service",
+        0xbe => "Network Monitor Agent",
+        0xbf => "Network Monitor Application",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// A NetBIOS name: up to 15 bytes, padded with spaces, and a suffix byte
+/// telling the service. Names in NBNS messages carry a scope as well.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NetbiosName {
+    #[serde(serialize_with = "serialize_lossy")]
+    pub name: Vec<u8>,
+    pub suffix: u8,
+    pub suffix_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+}
+
+impl NetbiosName {
+    pub fn new(name: &[u8], suffix: u8, scope: String) -> NetbiosName {
+        let len = name
+            .iter()
+            .rposition(|&c| c != b' ' && c != 0)
+            .map_or(0, |i| i + 1);
+        NetbiosName {
+            name: name[..len].to_vec(),
+            suffix,
+            suffix_type: netbios_suffix_type(suffix),
+            scope,
+        }
+    }
+
+    /// Undo the first-level encoding (RFC 1001 14.1), where each half
+    /// byte of the 16 byte name is a letter from `A` to `P`. `None` for
+    /// names that are not encoded so.
+    pub fn decode(name: &DomainName) -> Option<NetbiosName> {
+        let mut labels = name.labels();
+        let encoded = labels.next()?.to_ascii_uppercase();
+        if encoded.len() != 32 || !encoded.iter().all(|c| (b'A'..=b'P').contains(c)) {
+            return None;
+        }
+        let bytes: Vec<u8> = encoded
+            .chunks(2)
+            .map(|pair| ((pair[0] - b'A') << 4) | (pair[1] - b'A'))
+            .collect();
+        let scope: Vec<String> = labels
+            .map(|l| String::from_utf8_lossy(l).into_owned())
+            .collect();
+        Some(NetbiosName::new(&bytes[..15], bytes[15], scope.join(".")))
+    }
+
+    /// The name padded to 15 bytes, `*` with NUL and others with spaces.
+    fn padded(&self) -> Vec<u8> {
+        let pad = if self.name == b"*" { 0 } else { b' ' };
+        let mut padded = self.name.clone();
+        padded.resize(15, pad);
+        padded
+    }
+}
+
+/// `WS01<20>`, `WORKGROUP<1d>.scope`. Non-printable bytes are escaped.
+impl std::fmt::Display for NetbiosName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for &c in &self.name {
+            write!(f, "{}", std::ascii::escape_default(c))?;
+        }
+        write!(f, "<{:02x}>", self.suffix)?;
+        if !self.scope.is_empty() {
+            write!(f, ".{}", self.scope)?;
+        }
+        Ok(())
+    }
+}
+
+/// How a NetBIOS node resolves names (RFC 1001 10).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NbNodeType {
+    Broadcast,
+    PointToPoint,
+    Mixed,
+    Hybrid,
+}
+
+impl From<u16> for NbNodeType {
+    /// The ONT bits of NB_FLAGS.
+    fn from(flags: u16) -> NbNodeType {
+        match (flags >> 13) & 3 {
+            0 => NbNodeType::Broadcast,
+            1 => NbNodeType::PointToPoint,
+            2 => NbNodeType::Mixed,
+            _ => NbNodeType::Hybrid,
+        }
+    }
+}
+
+impl std::fmt::Display for NbNodeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            NbNodeType::Broadcast => "B",
+            NbNodeType::PointToPoint => "P",
+            NbNodeType::Mixed => "M",
+            NbNodeType::Hybrid => "H",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Serialize for NbNodeType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NbAddress {
+    pub group: bool,
+    pub node_type: NbNodeType,
+    pub address: Ipv4Addr,
+}
+
+/// NB rdata: the addresses owning a name.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NbRecord {
+    pub addresses: Vec<NbAddress>,
+}
+
+impl NbRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<NbRecord, DnsParseError> {
+        if rr.rdata.len() % 6 != 0 {
+            return Err(rr.invalid_rdata(full_dns_message));
+        }
+        let addresses = rr
+            .rdata
+            .chunks_exact(6)
+            .map(|entry| {
+                let flags = u16::from_be_bytes([entry[0], entry[1]]);
+                NbAddress {
+                    group: flags & 0x8000 != 0,
+                    node_type: NbNodeType::from(flags),
+                    address: Ipv4Addr::new(entry[2], entry[3], entry[4], entry[5]),
+                }
+            })
+            .collect();
+        Ok(NbRecord { addresses })
+    }
+
+    pub(crate) fn to_wire(&self) -> Vec<u8> {
+        let mut wire = Vec::with_capacity(self.addresses.len() * 6);
+        for nb in &self.addresses {
+            let ont = match nb.node_type {
+                NbNodeType::Broadcast => 0,
+                NbNodeType::PointToPoint => 1,
+                NbNodeType::Mixed => 2,
+                NbNodeType::Hybrid => 3,
+            };
+            let flags: u16 = (u16::from(nb.group) << 15) | (ont << 13);
+            wire.extend_from_slice(&flags.to_be_bytes());
+            wire.extend_from_slice(&nb.address.octets());
+        }
+        wire
+    }
+}
+
+/// `192.0.2.5(H) 192.0.2.6(B,group)`
+impl std::fmt::Display for NbRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, nb) in self.addresses.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}({}", nb.address, nb.node_type)?;
+            if nb.group {
+                write!(f, ",group")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// A name in a node status response, with its NAME_FLAGS.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NbstatName {
+    pub name: NetbiosName,
+    pub flags: u16,
+}
+
+impl NbstatName {
+    pub fn group(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+}
+
+/// NBSTAT rdata: the names registered on a node and its statistics,
+/// which start with the MAC address (RFC 1002 4.2.18).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NbstatRecord {
+    pub names: Vec<NbstatName>,
+    #[serde(serialize_with = "serialize_hex")]
+    pub statistics: Vec<u8>,
+}
+
+impl NbstatRecord {
+    pub fn new(
+        rr: &ResourceRecord,
+        full_dns_message: &[u8],
+    ) -> Result<NbstatRecord, DnsParseError> {
+        let (&count, entries) = rr
+            .rdata
+            .split_first()
+            .ok_or_else(|| rr.invalid_rdata(full_dns_message))?;
+        let len = usize::from(count) * 18;
+        if entries.len() < len {
+            return Err(rr.invalid_rdata(full_dns_message));
+        }
+        let names = entries[..len]
+            .chunks_exact(18)
+            .map(|entry| NbstatName {
+                name: NetbiosName::new(&entry[..15], entry[15], String::new()),
+                flags: u16::from_be_bytes([entry[16], entry[17]]),
+            })
+            .collect();
+        Ok(NbstatRecord {
+            names,
+            statistics: entries[len..].to_vec(),
+        })
+    }
+
+    /// The MAC address of the node, if the statistics are present.
+    pub fn unit_id(&self) -> Option<String> {
+        let unit_id = self.statistics.get(..6)?;
+        let octets: Vec<String> = unit_id.iter().map(|b| format!("{:02x}", b)).collect();
+        Some(octets.join(":"))
+    }
+
+    pub(crate) fn to_wire(&self) -> Vec<u8> {
+        let mut wire = vec![self.names.len() as u8];
+        for entry in &self.names {
+            wire.extend_from_slice(&entry.name.padded());
+            wire.push(entry.name.suffix);
+            wire.extend_from_slice(&entry.flags.to_be_bytes());
+        }
+        wire.extend_from_slice(&self.statistics);
+        wire
+    }
+}
+
+/// `WS01<00> WORKGROUP<00,group> unit=00:11:22:33:44:55`
+impl std::fmt::Display for NbstatRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, entry) in self.names.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", entry.name)?;
+            if entry.group() {
+                write!(f, "(group)")?;
+            }
+        }
+        if let Some(unit_id) = self.unit_id() {
+            write!(f, " unit={}", unit_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a NetBIOS Name Service message. NBNS uses the dns message
+/// layout, but NB and NBSTAT rdata is decoded as NBNS defines it.
+pub fn nbns_message(data: &[u8]) -> Result<(&[u8], DnsMessage<'_>), DnsParseError> {
+    let (rest, mut dns_message) = dns_message(data, false)?;
+    let sections = [
+        (Section::Answer, &mut dns_message.answers),
+        (Section::Authority, &mut dns_message.nsrecords),
+        (Section::Additional, &mut dns_message.arecords),
+    ];
+    for (section, records) in sections {
+        for (i, rr) in records.iter_mut().enumerate() {
+            let record = match (&rr.rrclass, u16::from(&rr.rrtype)) {
+                (DnsClass::IN, NBNS_NB) => NbRecord::new(rr, data).map(RRecordTypes::NB),
+                (DnsClass::IN, NBNS_NBSTAT) => {
+                    NbstatRecord::new(rr, data).map(RRecordTypes::NBSTAT)
+                }
+                _ => {
+                    rr.resolve_at(data, section, i);
+                    continue;
+                }
+            };
+            rr.record = Some(record.unwrap_or_else(|e| {
+                RRecordTypes::Malformed(MalformedRecord {
+                    raw: RawRecord::new(rr),
+                    error: e.at(section, i),
+                })
+            }));
+        }
+    }
+    Ok((rest, dns_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A positive name query response for `WS01<20>` from 192.0.2.5.
+    const NBNS_RESPONSE: &[u8] = b"\x80\x01\x85\x00\x00\x00\x00\x01\x00\x00\x00\x00\
+        \x20FHFDDADBCACACACACACACACACACACACA\x00\
+        \x00\x20\x00\x01\x00\x04\x93\xe0\x00\x06\x60\x00\xc0\x00\x02\x05";
+
+    #[test]
+    fn test_netbios_name() {
+        let name: DomainName = "FHFDDADBCACACACACACACACACACACACA.corp".parse().unwrap();
+        let nb = NetbiosName::decode(&name).unwrap();
+        assert_eq!(b"WS01", &nb.name[..]);
+        assert_eq!(0x20, nb.suffix);
+        assert_eq!(Some("File Server Service"), nb.suffix_type);
+        assert_eq!("WS01<20>.corp", nb.to_string());
+
+        let msbrowse = NetbiosName::new(b"\x01\x02__MSBROWSE__\x02", 0x01, String::new());
+        assert_eq!("\\x01\\x02__MSBROWSE__\\x02<01>", msbrowse.to_string());
+        assert_eq!(None, NetbiosName::decode(&"wpad.local".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_nbns_message() {
+        let (_rest, msg) = nbns_message(NBNS_RESPONSE).unwrap();
+        let rr = &msg.answers[0];
+        assert_eq!("NB", nbns_type_name(&rr.rrtype));
+        assert_eq!(
+            "WS01<20>",
+            NetbiosName::decode(&rr.name).unwrap().to_string()
+        );
+        let nb = match &rr.record {
+            Some(RRecordTypes::NB(nb)) => nb,
+            record => panic!("{:?}", record),
+        };
+        assert_eq!("192.0.2.5(H)", nb.to_string());
+        assert_eq!(&rr.rdata[..], &nb.to_wire()[..]);
+    }
+
+    #[test]
+    fn test_nbstat_record() {
+        let mut rdata = vec![2];
+        rdata.extend_from_slice(b"WS01           \x00\x04\x00");
+        rdata.extend_from_slice(b"WORKGROUP      \x00\x84\x00");
+        rdata.extend_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0, 0]);
+        let mut data = b"\x00\x00\x21\x00\x01\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(&rdata);
+        let (_, rr) = parse_resource_record(&data, &data).unwrap();
+        let nbstat = NbstatRecord::new(&rr, &data).unwrap();
+        assert_eq!(
+            "WS01<00> WORKGROUP<00>(group) unit=00:11:22:33:44:55",
+            nbstat.to_string()
+        );
+        assert_eq!(rdata, nbstat.to_wire());
+    }
+}
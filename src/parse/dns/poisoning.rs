@@ -0,0 +1,261 @@
+use crate::parse::dns::*;
+use serde_derive::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+/// A host answering LLMNR or NBNS queries for names it does not own, as
+/// poisoning tools like Responder do.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PoisoningAlert {
+    pub protocol: NameProtocol,
+    pub responder: IpAddr,
+    pub names: Vec<String>,
+}
+
+impl std::fmt::Display for PoisoningAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "possible {} poisoning by {}: answered for {}",
+            self.protocol,
+            self.responder,
+            self.names.join(", ")
+        )
+    }
+}
+
+/// Names kept per host, hosts register only a few.
+const MAX_OWNED_NAMES: usize = 64;
+
+#[derive(Clone, Debug, Default)]
+struct Responder {
+    owned: HashSet<String>,
+    answered: BTreeSet<String>,
+    reported: bool,
+}
+
+/// Counts the names each host answers LLMNR and NBNS queries for. A
+/// host only answers for its own names, so one that answers for many is
+/// reported. Names a host has registered with NBNS count as its own.
+/// At most `capacity` hosts are kept, the oldest are dropped first.
+#[derive(Clone, Debug)]
+pub struct PoisoningDetector {
+    threshold: usize,
+    capacity: usize,
+    responders: HashMap<(NameProtocol, IpAddr), Responder>,
+    order: VecDeque<(NameProtocol, IpAddr)>,
+}
+
+impl PoisoningDetector {
+    /// Report hosts once they have answered for `threshold` names.
+    pub fn new(threshold: usize, capacity: usize) -> PoisoningDetector {
+        PoisoningDetector {
+            threshold,
+            capacity,
+            responders: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn responder(&mut self, key: (NameProtocol, IpAddr)) -> Option<&mut Responder> {
+        if self.capacity == 0 {
+            return None;
+        }
+        if !self.responders.contains_key(&key) {
+            self.order.push_back(key);
+            if self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.responders.remove(&oldest);
+                }
+            }
+        }
+        Some(self.responders.entry(key).or_default())
+    }
+
+    /// Add a message sent by `src`. Returns an alert the first time the
+    /// host reaches the threshold.
+    pub fn add_message(
+        &mut self,
+        protocol: NameProtocol,
+        src: IpAddr,
+        dns: &DnsMessage,
+    ) -> Option<PoisoningAlert> {
+        if protocol != NameProtocol::Llmnr && protocol != NameProtocol::Nbns {
+            return None;
+        }
+        // Only responders and registering hosts are kept track of, not
+        // every host that asks.
+        let flags = &dns.header.flags;
+        if flags.qr == Qr::Query {
+            let registration = matches!(
+                flags.opcode,
+                Opcode::Update | Opcode::Other(8) | Opcode::Other(9) | Opcode::Other(15)
+            );
+            if protocol == NameProtocol::Nbns && registration {
+                if let Some(responder) = self.responder((protocol, src)) {
+                    for query in &dns.queries {
+                        if responder.owned.len() < MAX_OWNED_NAMES {
+                            responder.owned.insert(owner_name(protocol, &query.name));
+                        }
+                    }
+                }
+            }
+            return None;
+        }
+        if flags.rcode != Rcode::NoError || dns.answers.is_empty() {
+            return None;
+        }
+        // NBNS name servers (WINS) answer for other hosts and set RA
+        // (RFC 1002 4.2.1.1), and WACK responses only ask to wait.
+        if protocol == NameProtocol::Nbns && (flags.ra || flags.opcode == Opcode::Other(7)) {
+            return None;
+        }
+        let threshold = self.threshold;
+        let responder = self.responder((protocol, src))?;
+        if responder.reported {
+            return None;
+        }
+        for rr in &dns.answers {
+            let name = owner_name(protocol, &rr.name);
+            if !responder.owned.contains(&name) {
+                responder.answered.insert(name);
+            }
+        }
+        if responder.answered.len() < threshold {
+            return None;
+        }
+        responder.reported = true;
+        Some(PoisoningAlert {
+            protocol,
+            responder: src,
+            names: responder.answered.iter().cloned().collect(),
+        })
+    }
+}
+
+/// The name as a host owns it: NetBIOS names without suffix and scope,
+/// which are case-insensitive like LLMNR names.
+fn owner_name(protocol: NameProtocol, name: &DomainName) -> String {
+    match NetbiosName::decode(name) {
+        Some(nb) if protocol == NameProtocol::Nbns => {
+            String::from_utf8_lossy(&nb.name).to_uppercase()
+        }
+        _ => name.to_string().to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(names: &[&str]) -> OwnedDnsMessage {
+        let flags = Flags {
+            qr: Qr::Response,
+            ..Default::default()
+        };
+        let mut builder = DnsMessageBuilder::new(1).flags(flags);
+        for name in names {
+            builder = builder.answer(ResourceRecord::new(
+                name.parse().unwrap(),
+                DnsClass::IN,
+                30,
+                RRecordTypes::A(ARecord {
+                    address: "192.0.2.66".parse().unwrap(),
+                }),
+            ));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_llmnr_poisoning() {
+        let responder: IpAddr = "192.0.2.66".parse().unwrap();
+        let host: IpAddr = "192.0.2.10".parse().unwrap();
+        let mut detector = PoisoningDetector::new(3, 10);
+        let llmnr = NameProtocol::Llmnr;
+        let mut query = response(&[]);
+        query.header.flags.qr = Qr::Query;
+        assert_eq!(None, detector.add_message(llmnr, host, &query));
+        assert!(detector.responders.is_empty());
+        for _ in 0..5 {
+            let msg = response(&["ws10"]);
+            assert_eq!(None, detector.add_message(llmnr, host, &msg));
+        }
+        assert_eq!(
+            None,
+            detector.add_message(llmnr, responder, &response(&["wpad", "WPAD"]))
+        );
+        let alert = detector
+            .add_message(llmnr, responder, &response(&["filesrv", "printer"]))
+            .unwrap();
+        assert_eq!(
+            "possible LLMNR poisoning by 192.0.2.66: answered for filesrv, printer, wpad",
+            alert.to_string()
+        );
+        let msg = response(&["intranet"]);
+        assert_eq!(None, detector.add_message(llmnr, responder, &msg));
+        assert_eq!(
+            None,
+            detector.add_message(NameProtocol::Mdns, responder, &msg)
+        );
+    }
+
+    #[test]
+    fn test_nbns_registered_names() {
+        let host: IpAddr = "192.0.2.10".parse().unwrap();
+        let nbns = NameProtocol::Nbns;
+        let names = [
+            "FHFDDBDACACACACACACACACACACACAAA",
+            "FHFDDBDACACACACACACACACACACACACA",
+            "FHEPFCELEHFCEPFFFACACACACACACAAA",
+        ];
+        let flags = Flags {
+            opcode: Opcode::Update,
+            ..Default::default()
+        };
+        let mut builder = DnsMessageBuilder::new(1).flags(flags);
+        for name in &names[..2] {
+            builder = builder.query(name.parse().unwrap(), DnsType::from(NBNS_NB), DnsClass::IN);
+        }
+        let mut detector = PoisoningDetector::new(2, 10);
+        assert_eq!(None, detector.add_message(nbns, host, &builder.build()));
+        assert_eq!(None, detector.add_message(nbns, host, &response(&names)));
+    }
+
+    #[test]
+    fn test_nbns_name_server() {
+        let server: IpAddr = "192.0.2.2".parse().unwrap();
+        let nbns = NameProtocol::Nbns;
+        let names = [
+            "FHFDDBDACACACACACACACACACACACAAA",
+            "FHFDDBDBCACACACACACACACACACACAAA",
+            "FHEPFCELEHFCEPFFFACACACACACACAAA",
+        ];
+        let mut detector = PoisoningDetector::new(2, 10);
+        let mut wins = response(&names);
+        wins.header.flags.ra = true;
+        assert_eq!(None, detector.add_message(nbns, server, &wins));
+        let mut wack = response(&names);
+        wack.header.flags.opcode = Opcode::Other(7);
+        assert_eq!(None, detector.add_message(nbns, server, &wack));
+        assert!(detector.responders.is_empty());
+        assert!(detector
+            .add_message(nbns, server, &response(&names))
+            .is_some());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let llmnr = NameProtocol::Llmnr;
+        let mut detector = PoisoningDetector::new(2, 2);
+        let first: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(None, detector.add_message(llmnr, first, &response(&["a"])));
+        for host in 2..=3 {
+            let host = IpAddr::from([192, 0, 2, host]);
+            assert_eq!(None, detector.add_message(llmnr, host, &response(&["a"])));
+        }
+        assert_eq!(2, detector.responders.len());
+        // The first host was forgotten, so it starts over.
+        assert_eq!(None, detector.add_message(llmnr, first, &response(&["b"])));
+    }
+}
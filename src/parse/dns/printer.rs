@@ -1,46 +1,55 @@
 use crate::parse::dns::*;
+use crate::parse::packet::*;
 use log::debug;
-use pnet::packet::{
-    ethernet::{EtherTypes, EthernetPacket},
-    ip::IpNextHeaderProtocols,
-    ipv4::Ipv4Packet,
-    udp::UdpPacket,
-    Packet,
-};
 use serde_derive::Serialize;
 use serde_json::Error;
+use std::net::{IpAddr, SocketAddr};
+
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+/// NBNS names are shown decoded, e.g. `WS01<20>`.
+fn nbns_name(name: &DomainName) -> String {
+    match NetbiosName::decode(name) {
+        Some(nb) => nb.to_string(),
+        None => name.to_string(),
+    }
+}
 
 #[derive(Debug, PartialEq, Serialize)]
 pub(crate) struct ResourceRecordPrinter {
     name: String,
     rrtype: String,
     rrclass: String,
+    #[serde(skip_serializing_if = "is_false")]
+    cache_flush: bool,
     ttl: u32,
     rdata: RRecordTypes,
 }
 
 impl ResourceRecordPrinter {
-    pub fn from_rr(rr: &ResourceRecord) -> ResourceRecordPrinter {
+    pub fn from_rr(rr: &ResourceRecord, protocol: NameProtocol) -> ResourceRecordPrinter {
         let rrclass = match rr.rrclass {
             DnsClass::OtherUsage(_) => String::from("*"),
-            _ => format!("{:?}", rr.rrclass),
+            _ => rr.rrclass.to_string(),
         };
 
-        let rrtype = format!("{:?}", rr.rrtype);
-        let name: String = match &rr.name_chain.name {
-            Some(name) => name.to_string(),
-            None => String::new(),
+        let (rrtype, name) = match protocol {
+            NameProtocol::Nbns => (nbns_type_name(&rr.rrtype), nbns_name(&rr.name)),
+            _ => (rr.rrtype.to_string(), rr.name.to_string()),
         };
 
         let rdata = match rr.record.to_owned() {
             Some(record) => record,
-            None => RRecordTypes::ParserNotImpl,
+            None => RRecordTypes::Raw(RawRecord::new(rr)),
         };
 
         ResourceRecordPrinter {
             name,
             rrclass,
             rrtype,
+            cache_flush: rr.cache_flush,
             ttl: rr.ttl,
             rdata,
         }
@@ -49,10 +58,11 @@ impl ResourceRecordPrinter {
 
 impl std::fmt::Display for ResourceRecordPrinter {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let flush = if self.cache_flush { "+flush" } else { "" };
         write!(
             f,
-            "{}/{}/{}/{}({})",
-            self.rrclass, self.ttl, self.rrtype, self.name, self.rdata
+            "{}{}/{}/{}/{}({})",
+            self.rrclass, flush, self.ttl, self.rrtype, self.name, self.rdata
         )
     }
 }
@@ -60,26 +70,29 @@ impl std::fmt::Display for ResourceRecordPrinter {
 #[derive(Debug, PartialEq, Serialize)]
 pub(crate) struct QueryPrinter {
     qclass: String,
+    #[serde(skip_serializing_if = "is_false")]
+    unicast_response: bool,
     qtype: String,
     qname: String,
 }
 
 impl std::fmt::Display for QueryPrinter {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}/{}/{}", self.qclass, self.qtype, self.qname)
+        let qu = if self.unicast_response { "+QU" } else { "" };
+        write!(f, "{}{}/{}/{}", self.qclass, qu, self.qtype, self.qname)
     }
 }
 
 impl QueryPrinter {
-    pub fn from_query(q: &Query) -> QueryPrinter {
-        let qclass = format!("{:?}", q.qclass);
-        let qtype = format!("{:?}", q.qtype);
-        let qname: String = match &q.name_chain.name {
-            Some(name) => name.to_string(),
-            None => String::new(),
+    pub fn from_query(q: &Query, protocol: NameProtocol) -> QueryPrinter {
+        let qclass = q.qclass.to_string();
+        let (qtype, qname) = match protocol {
+            NameProtocol::Nbns => (nbns_type_name(&q.qtype), nbns_name(&q.name)),
+            _ => (q.qtype.to_string(), q.name.to_string()),
         };
         QueryPrinter {
             qclass,
+            unicast_response: q.unicast_response,
             qtype,
             qname,
         }
@@ -89,6 +102,12 @@ impl QueryPrinter {
 #[derive(Debug, PartialEq, Serialize)]
 pub(crate) struct PrinterVec<T>(Vec<T>);
 
+impl<T> PrinterVec<T> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 impl<D: std::fmt::Display> std::fmt::Display for PrinterVec<D> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let len = self.0.len();
@@ -106,102 +125,198 @@ impl<D: std::fmt::Display> std::fmt::Display for PrinterVec<D> {
 pub struct PacketPrinter {
     ts: String,
     proto: String,
+    protocol: NameProtocol,
     src: String,
     sport: u16,
     dest: String,
     dport: u16,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    vlans: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interface: Option<u32>,
+    #[serde(skip_serializing_if = "PrinterVec::is_empty")]
+    tunnels: PrinterVec<Tunnel>,
     qid: u16,
     opcode: String,
     qr: String,
     rcode: String,
+    flags: Vec<&'static str>,
     queries: PrinterVec<QueryPrinter>,
     answers: PrinterVec<ResourceRecordPrinter>,
     nsrecords: PrinterVec<ResourceRecordPrinter>,
     arecords: PrinterVec<ResourceRecordPrinter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prerequisites: Option<PrinterVec<UpdateEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updates: Option<PrinterVec<UpdateEntry>>,
+    #[serde(skip_serializing_if = "PrinterVec::is_empty")]
+    ede: PrinterVec<OptionExtendedError>,
+    #[serde(skip_serializing_if = "PrinterVec::is_empty")]
+    violations: PrinterVec<Violation>,
 }
 
 impl PacketPrinter {
+    /// `rest` is what was left after the dns message.
     pub fn new(
         packet: &pcap::Packet,
-        ipv4: &Ipv4Packet,
-        udp: &UdpPacket,
+        payload: &DnsPayload,
         dns: &DnsMessage,
+        rest: &[u8],
     ) -> PacketPrinter {
         debug!("{:#?}", dns);
         let ts = format!("{}.{}", packet.header.ts.tv_sec, packet.header.ts.tv_usec);
-        let proto = String::from("UDP");
-        let src = format!("{}", ipv4.get_source());
-        let sport = udp.get_source();
-        let dest = format!("{}", ipv4.get_destination());
-        let dport = udp.get_destination();
+        let proto = payload.transport.to_string();
+        let src = payload.src.to_string();
+        let sport = payload.sport;
+        let dest = payload.dest.to_string();
+        let dport = payload.dport;
+        let vlans = payload.vlans.clone();
+        let interface = payload.interface;
+        let tunnels = PrinterVec(payload.tunnels.clone());
+        let protocol = NameProtocol::from_ports(sport, dport);
         let qid = dns.header.id;
-        let opcode = format!("{:?}", dns.header.flags.opcode);
+        let opcode = protocol.opcode_name(&dns.header.flags.opcode);
         let qr = format!("{:?}", dns.header.flags.qr);
         let rcode = format!("{:?}", dns.header.flags.rcode);
+        let flags = protocol.flag_names(&dns.header.flags);
 
         let mut queries: PrinterVec<QueryPrinter> = PrinterVec(Vec::new());
         for q in dns.queries.iter() {
-            let qp = QueryPrinter::from_query(q);
+            let qp = QueryPrinter::from_query(q, protocol);
             queries.0.push(qp);
         }
 
         let mut answers: PrinterVec<ResourceRecordPrinter> = PrinterVec(Vec::new());
         for rr in dns.answers.iter() {
-            let rp = ResourceRecordPrinter::from_rr(rr);
+            let rp = ResourceRecordPrinter::from_rr(rr, protocol);
             answers.0.push(rp);
         }
 
         let mut nsrecords: PrinterVec<ResourceRecordPrinter> = PrinterVec(Vec::new());
         for rr in dns.nsrecords.iter() {
-            let rp = ResourceRecordPrinter::from_rr(rr);
+            let rp = ResourceRecordPrinter::from_rr(rr, protocol);
             nsrecords.0.push(rp);
         }
 
         let mut arecords: PrinterVec<ResourceRecordPrinter> = PrinterVec(Vec::new());
         for rr in dns.arecords.iter() {
-            let rp = ResourceRecordPrinter::from_rr(rr);
+            let rp = ResourceRecordPrinter::from_rr(rr, protocol);
             arecords.0.push(rp);
         }
 
+        // UPDATE messages show their operations instead of the answer and
+        // authority sections. NBNS uses the UPDATE opcode for registration.
+        let update = match protocol {
+            NameProtocol::Nbns => None,
+            _ => dns.update(),
+        };
+        let (prerequisites, updates) = match update {
+            Some(update) => {
+                answers.0.clear();
+                nsrecords.0.clear();
+                (
+                    Some(PrinterVec(update.prerequisites)),
+                    Some(PrinterVec(update.updates)),
+                )
+            }
+            None => (None, None),
+        };
+
+        let ede = PrinterVec(dns.extended_errors().into_iter().cloned().collect());
+        let violations = PrinterVec(dns.validate(rest));
+
         PacketPrinter {
             ts,
             proto,
+            protocol,
             src,
             sport,
             dest,
             dport,
+            vlans,
+            interface,
+            tunnels,
             qid,
             opcode,
             qr,
             rcode,
+            flags,
             queries,
             answers,
             nsrecords,
             arecords,
+            prerequisites,
+            updates,
+            ede,
+            violations,
         }
     }
 
-    pub fn parse_packet(packet: &pcap::Packet) -> Option<PacketPrinter> {
-        let ethernet = EthernetPacket::new(packet.data).unwrap();
-        match ethernet.get_ethertype() {
-            EtherTypes::Ipv4 => {
-                let ipv4_packet = Ipv4Packet::new(ethernet.payload()).unwrap();
-                match ipv4_packet.get_next_level_protocol() {
-                    IpNextHeaderProtocols::Udp => {
-                        let udp_packet = UdpPacket::new(ipv4_packet.payload()).unwrap();
-                        let (_rest, dns_message) = dns_message(udp_packet.payload(), true).unwrap();
-                        Some(PacketPrinter::new(
-                            packet,
-                            &ipv4_packet,
-                            &udp_packet,
-                            &dns_message,
-                        ))
-                    }
-                    _ => None,
-                }
-            }
-            _ => None,
+    /// Decode a packet captured with link type `linktype`.
+    ///
+    /// Returns `Ok(None)` for packets that do not carry a dns message over
+    /// UDP or TCP and an error when the dns message itself is malformed.
+    /// Messages that end early are decoded as far as they go, the missing
+    /// records are reported as violations. Packets from tunnels, which a
+    /// capture filter can not look into, are skipped unless they are sent
+    /// to or from a name service port.
+    pub fn parse_packet(
+        packet: &pcap::Packet,
+        linktype: pcap::Linktype,
+    ) -> Result<Option<PacketPrinter>, DnsParseError> {
+        Ok(PacketPrinter::parse_packet_message(packet, linktype)?.map(|(printer, _)| printer))
+    }
+
+    /// Like `parse_packet`, also returning the decoded message, which
+    /// borrows from the packet.
+    pub fn parse_packet_message<'a>(
+        packet: &pcap::Packet<'a>,
+        linktype: pcap::Linktype,
+    ) -> Result<Option<(PacketPrinter, DnsMessage<'a>)>, DnsParseError> {
+        let payload = match link_payload(linktype, packet.data) {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+        if !payload.tunnels.is_empty()
+            && !NameProtocol::is_name_port(payload.sport)
+            && !NameProtocol::is_name_port(payload.dport)
+        {
+            return Ok(None);
         }
+        let (rest, dns_message) = match NameProtocol::from_ports(payload.sport, payload.dport) {
+            NameProtocol::Nbns => nbns_message(payload.payload)?,
+            _ => dns_message_partial(payload.payload, true)?,
+        };
+        let printer = PacketPrinter::new(packet, &payload, &dns_message, rest);
+        Ok(Some((printer, dns_message)))
+    }
+
+    pub fn protocol(&self) -> NameProtocol {
+        self.protocol
+    }
+
+    /// The source address of the packet.
+    pub fn source(&self) -> Option<IpAddr> {
+        self.src.parse().ok()
+    }
+
+    /// The source and destination of the packet.
+    pub fn endpoints(&self) -> Option<(SocketAddr, SocketAddr)> {
+        let src = SocketAddr::new(self.src.parse().ok()?, self.sport);
+        let dest = SocketAddr::new(self.dest.parse().ok()?, self.dport);
+        Some((src, dest))
+    }
+
+    /// Add violations found outside the message, e.g. by comparing it to
+    /// its query.
+    pub fn add_violations(&mut self, violations: Vec<Violation>) {
+        self.violations.0.extend(violations);
+    }
+
+    /// True if any question asks for `qtype`.
+    pub fn has_qtype(&self, qtype: &DnsType) -> bool {
+        let qtype = qtype.to_string();
+        self.queries.0.iter().any(|q| q.qtype == qtype)
     }
 
     pub fn to_json(&self) -> Result<String, Error> {
@@ -209,22 +324,36 @@ impl PacketPrinter {
     }
 }
 
+/// `addr:port`, with IPv6 addresses in brackets.
+fn endpoint(addr: &str, port: u16) -> String {
+    if addr.contains(':') {
+        format!("[{}]:{}", addr, port)
+    } else {
+        format!("{}:{}", addr, port)
+    }
+}
+
 #[allow(clippy::format_in_format_args)]
 impl std::fmt::Display for PacketPrinter {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "{:18} {:3} {:>21} -> {:<21} {:6} {:>7}/{:<10} {:10}\t{}{}{}{}",
+            "{:18} {:3} {:>21} -> {:<21} {:6} {:>7}/{:<10} {:10} {:<17}\t{}{}{}{}{}{}{}{}{}{}",
             self.ts,
-            self.proto,
-            format!("{}:{}", self.src, self.sport),
-            format!("{}:{}", self.dest, self.dport),
+            match self.protocol {
+                NameProtocol::Dns => self.proto.clone(),
+                protocol => format!("{}/{}", self.proto, protocol),
+            },
+            endpoint(&self.src, self.sport),
+            endpoint(&self.dest, self.dport),
             self.qid,
             self.opcode,
             self.qr,
             self.rcode,
-            match self.queries.0.len() {
-                0 => "".to_string(),
+            self.flags.join(","),
+            match (self.queries.0.len(), &self.updates) {
+                (0, _) => "".to_string(),
+                (_, Some(_)) => format!("{:<40}", format!("\tzone:|{}|", self.queries)),
                 _ => format!("{:<40}", format!("\tq:|{}|", self.queries)),
             },
             match self.answers.0.len() {
@@ -235,10 +364,48 @@ impl std::fmt::Display for PacketPrinter {
                 0 => "".to_string(),
                 _ => format!("\tns:|{}|", self.nsrecords),
             },
+            match (&self.prerequisites, &self.updates) {
+                (Some(prerequisites), Some(updates)) => format!(
+                    "{}\tupdate:|{}|",
+                    match prerequisites.0.len() {
+                        0 => "".to_string(),
+                        _ => format!("\tprereq:|{}|", prerequisites),
+                    },
+                    updates
+                ),
+                _ => "".to_string(),
+            },
             match self.arecords.0.len() {
                 0 => "".to_string(),
                 _ => format!("\tar:|{}|", self.arecords),
             },
+            match self.ede.0.len() {
+                0 => "".to_string(),
+                _ => format!("\tede:|{}|", self.ede),
+            },
+            match self.violations.0.len() {
+                0 => "".to_string(),
+                _ => format!("\tviolations:|{}|", self.violations),
+            },
+            match self.vlans.len() {
+                0 => "".to_string(),
+                _ => format!(
+                    "\tvlan:|{}|",
+                    self.vlans
+                        .iter()
+                        .map(|vlan| vlan.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            },
+            match self.interface {
+                Some(interface) => format!("\tifindex:|{}|", interface),
+                None => "".to_string(),
+            },
+            match self.tunnels.0.len() {
+                0 => "".to_string(),
+                _ => format!("\ttunnel:|{}|", self.tunnels),
+            },
         )
     }
 }
@@ -254,11 +421,13 @@ mod tests {
     fn test_print_text() {
         let mut cap = pcap::Capture::from_file("fixtures/dns/dns.pcap").unwrap();
         cap.filter(BPF, true).unwrap();
+        let linktype = cap.get_datalink();
         for i in 1..=PKTNO {
-            cap.next_packet().unwrap_or_else(|_| panic!("failed to get packet {}!", i));
+            cap.next_packet()
+                .unwrap_or_else(|_| panic!("failed to get packet {}!", i));
         }
         let pcap_pkt = cap.next_packet().expect("failed to get packet!");
-        if let Some(packet_printer) = PacketPrinter::parse_packet(&pcap_pkt) {
+        if let Ok(Some(packet_printer)) = PacketPrinter::parse_packet(&pcap_pkt, linktype) {
             println!("{}", packet_printer);
         } else {
             panic!("parse packet failed!");
@@ -269,15 +438,62 @@ mod tests {
     fn test_print_json() {
         let mut cap = pcap::Capture::from_file("fixtures/dns/dns.pcap").unwrap();
         cap.filter(BPF, true).unwrap();
+        let linktype = cap.get_datalink();
         for i in 1..=PKTNO {
-            cap.next_packet().unwrap_or_else(|_| panic!("failed to get packet {}!", i));
+            cap.next_packet()
+                .unwrap_or_else(|_| panic!("failed to get packet {}!", i));
         }
         let pcap_pkt = cap.next_packet().expect("failed to get packet!");
-        if let Some(packet_printer) = PacketPrinter::parse_packet(&pcap_pkt) {
-            println!("{}", packet_printer.to_json().unwrap());
+        if let Ok(Some(packet_printer)) = PacketPrinter::parse_packet(&pcap_pkt, linktype) {
+            let json = packet_printer.to_json().unwrap();
+            println!("{}", json);
+            assert!(json.contains(r#""flags":["qr","rd","ra"]"#));
         } else {
             panic!("parse packet failed!");
         }
     }
 
+    #[test]
+    fn test_print_mdns_bits() {
+        let mut rr = ResourceRecord::new(
+            "printer.local".parse().unwrap(),
+            DnsClass::IN,
+            120,
+            RRecordTypes::A(ARecord {
+                address: "192.0.2.7".parse().unwrap(),
+            }),
+        );
+        rr.cache_flush = true;
+        let rp = ResourceRecordPrinter::from_rr(&rr, NameProtocol::Mdns);
+        assert_eq!("IN+flush/120/A/printer.local(192.0.2.7)", rp.to_string());
+        assert!(serde_json::to_string(&rp)
+            .unwrap()
+            .contains(r#""rrclass":"IN","cache_flush":true"#));
+
+        let query = Query {
+            name: "_ipp._tcp.local".parse().unwrap(),
+            qtype: DnsType::PTR,
+            qclass: DnsClass::IN,
+            unicast_response: true,
+        };
+        assert_eq!(
+            "IN+QU/PTR/_ipp._tcp.local",
+            QueryPrinter::from_query(&query, NameProtocol::Mdns).to_string()
+        );
+    }
+
+    #[test]
+    fn test_print_ipv6_endpoints() {
+        let mut cap = pcap::Capture::from_file("fixtures/dns/dns_ipv6.pcap").unwrap();
+        let linktype = cap.get_datalink();
+        let pcap_pkt = cap.next_packet().expect("failed to get packet!");
+        let packet_printer = PacketPrinter::parse_packet(&pcap_pkt, linktype)
+            .unwrap()
+            .unwrap();
+        assert!(packet_printer
+            .to_string()
+            .contains("[2001:db8::53]:53 -> [2001:db8::1]:40000"));
+        let json = packet_printer.to_json().unwrap();
+        assert!(json.contains(r#""src":"2001:db8::53","sport":53"#));
+    }
 }
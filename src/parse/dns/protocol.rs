@@ -0,0 +1,126 @@
+use crate::parse::dns::*;
+use serde::{Serialize, Serializer};
+
+/// The protocols that use the dns message format, told apart by port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NameProtocol {
+    Dns,
+    /// Multicast DNS (RFC 6762), UDP/5353.
+    Mdns,
+    /// Link-Local Multicast Name Resolution (RFC 4795), UDP/5355.
+    Llmnr,
+    /// NetBIOS Name Service (RFC 1002), UDP/137.
+    Nbns,
+}
+
+impl NameProtocol {
+    /// True for the ports of the protocols above.
+    pub fn is_name_port(port: u16) -> bool {
+        matches!(port, 53 | 137 | 5353 | 5355)
+    }
+
+    pub fn from_ports(sport: u16, dport: u16) -> NameProtocol {
+        let either = |port| sport == port || dport == port;
+        if either(53) {
+            NameProtocol::Dns
+        } else if either(137) {
+            NameProtocol::Nbns
+        } else if either(5355) {
+            NameProtocol::Llmnr
+        } else if either(5353) {
+            NameProtocol::Mdns
+        } else {
+            NameProtocol::Dns
+        }
+    }
+
+    /// Names of the header bits that are set. LLMNR uses the AA and RD
+    /// bits as C (conflict) and T (tentative) and has no RA, AD or CD
+    /// (RFC 4795 2.1.1). NBNS calls the CD bit B (broadcast).
+    pub fn flag_names(&self, flags: &Flags) -> Vec<&'static str> {
+        let response = flags.qr == Qr::Response;
+        let bits = match self {
+            NameProtocol::Llmnr => vec![
+                (response, "qr"),
+                (flags.aa, "c"),
+                (flags.tc, "tc"),
+                (flags.rd, "t"),
+            ],
+            NameProtocol::Nbns => vec![
+                (response, "qr"),
+                (flags.aa, "aa"),
+                (flags.tc, "tc"),
+                (flags.rd, "rd"),
+                (flags.ra, "ra"),
+                (flags.cd, "b"),
+            ],
+            NameProtocol::Dns | NameProtocol::Mdns => return flags.names(),
+        };
+        bits.iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// The opcode name. NBNS has its own opcodes (RFC 1002 4.2.1.1).
+    pub fn opcode_name(&self, opcode: &Opcode) -> String {
+        match (self, opcode) {
+            (NameProtocol::Nbns, Opcode::Update) => String::from("Registration"),
+            (NameProtocol::Nbns, Opcode::Dso) => String::from("Release"),
+            (NameProtocol::Nbns, Opcode::Other(7)) => String::from("WACK"),
+            (NameProtocol::Nbns, Opcode::Other(8)) | (NameProtocol::Nbns, Opcode::Other(9)) => {
+                String::from("Refresh")
+            }
+            (NameProtocol::Nbns, Opcode::Other(15)) => String::from("MultiHomedRegistration"),
+            _ => format!("{:?}", opcode),
+        }
+    }
+}
+
+impl std::fmt::Display for NameProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            NameProtocol::Dns => "DNS",
+            NameProtocol::Mdns => "mDNS",
+            NameProtocol::Llmnr => "LLMNR",
+            NameProtocol::Nbns => "NBNS",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Serialize for NameProtocol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_flags() {
+        assert_eq!(NameProtocol::Llmnr, NameProtocol::from_ports(5355, 49152));
+        assert_eq!(NameProtocol::Nbns, NameProtocol::from_ports(137, 137));
+        assert_eq!(NameProtocol::Dns, NameProtocol::from_ports(53, 5353));
+        assert!(!NameProtocol::is_name_port(4789));
+        let flags = Flags {
+            qr: Qr::Response,
+            aa: true,
+            rd: true,
+            cd: true,
+            ..Default::default()
+        };
+        assert_eq!(vec!["qr", "c", "t"], NameProtocol::Llmnr.flag_names(&flags));
+        assert_eq!(
+            vec!["qr", "aa", "rd", "b"],
+            NameProtocol::Nbns.flag_names(&flags)
+        );
+        assert_eq!(
+            "Registration",
+            NameProtocol::Nbns.opcode_name(&Opcode::Update)
+        );
+        assert_eq!("Update", NameProtocol::Dns.opcode_name(&Opcode::Update));
+    }
+}
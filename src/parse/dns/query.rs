@@ -1,21 +1,41 @@
 use crate::parse::dns::*;
 use nom::*;
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Query<'a> {
-    pub name_chain: CompressedNameChain<'a>,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query {
+    pub name: DomainName,
     pub qtype: DnsType,
     pub qclass: DnsClass,
+    /// The mDNS QU bit, asking for a unicast response (RFC 6762 5.4).
+    pub unicast_response: bool,
 }
 
 #[rustfmt::skip]
-named!(pub (crate) parse_query<&[u8], Query>, do_parse!(
+named!(pub (crate) parse_query_fields<&[u8], (CompressedNameChain<'_>, DnsType, DnsClass)>, do_parse!(
     name_chain: parse_compressed_chain >>
     qtype: parse_dnstype >>
     qclass: parse_dnsclass >>
-    (Query{name_chain, qtype, qclass})
+    ((name_chain, qtype, qclass))
 ));
 
+/// Parse the question at the start of `i`, a slice of `full_dns_message`.
+pub(crate) fn parse_query<'a>(full_dns_message: &[u8], i: &'a [u8]) -> ParseResult<'a, Query> {
+    let offset = offset_in(full_dns_message, i).unwrap_or(0);
+    let (rest, (name_chain, qtype, qclass)) =
+        parse_query_fields(i).map_err(|e| DnsParseError::from_nom(e, full_dns_message, offset))?;
+    let name = name_chain.resolve_name(full_dns_message)?;
+    let (qclass, unicast_response) = qclass.split_mdns_bit();
+    Ok((
+        rest,
+        Query {
+            name,
+            qtype,
+            qclass,
+            unicast_response,
+        },
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -26,10 +46,20 @@ mod tests {
     fn test_parse_query() {
         let (offset, len) = (12, 16);
         let query = &DNS_RESPONSE[offset..offset + len];
-        let (_rest, query) = parse_query(query).unwrap();
+        let (_rest, query) = parse_query(DNS_RESPONSE, query).unwrap();
         println!("{:?}", query);
+        assert_eq!(query.name, "google.com".parse().unwrap());
         assert_eq!(query.qtype, DnsType::MX);
         assert_eq!(query.qclass, DnsClass::IN);
+        assert!(!query.unicast_response);
     }
 
+    #[test]
+    fn test_parse_mdns_query() {
+        let data = b"\x05_http\x04_tcp\x05local\x00\x00\x0c\x80\x01";
+        let (_rest, query) = parse_query(data, data).unwrap();
+        assert_eq!(query.qtype, DnsType::PTR);
+        assert_eq!(query.qclass, DnsClass::IN);
+        assert!(query.unicast_response);
+    }
 }
@@ -1,32 +1,34 @@
 use crate::parse::dns::*;
+use data_encoding::BASE64;
 use log::debug;
 use nom::*;
+use serde::Serializer;
 use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::fmt::Write;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct MxRecord {
     pub preference: u16,
-    pub exchange: String,
+    pub exchange: DomainName,
 }
 
 impl MxRecord {
-    named!(parse_rdata<&[u8], (u16, CompressedNameChain)>, do_parse!(
+    named!(parse_rdata<&[u8], (u16, CompressedNameChain<'_>)>, do_parse!(
         preference: be_u16 >>
         name_chain: parse_compressed_chain >>
         ((preference, name_chain))
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> MxRecord {
-        let (_, (preference, mut name_chain)) = Self::parse_rdata(rr.rdata).unwrap();
-        let exchange = match name_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_owned(),
-            None => String::new(),
-        };
-        MxRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<MxRecord, DnsParseError> {
+        let (_, (preference, name_chain)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let exchange = name_chain.resolve_name(full_dns_message)?;
+        Ok(MxRecord {
             preference,
             exchange,
-        }
+        })
     }
 }
 
@@ -38,7 +40,7 @@ impl std::fmt::Display for MxRecord {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct ARecord {
-    address: Ipv4Addr,
+    pub address: Ipv4Addr,
 }
 
 impl ARecord {
@@ -47,9 +49,10 @@ impl ARecord {
         (Ipv4Addr::from(address))
     ));
 
-    pub fn new(rr: &ResourceRecord) -> ARecord {
-        let (_, address) = Self::parse_rdata(rr.rdata).unwrap();
-        ARecord { address }
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<ARecord, DnsParseError> {
+        let (_, address) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(ARecord { address })
     }
 }
 
@@ -61,7 +64,7 @@ impl std::fmt::Display for ARecord {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct AAAARecord {
-    address: Ipv6Addr,
+    pub address: Ipv6Addr,
 }
 
 impl AAAARecord {
@@ -70,9 +73,10 @@ impl AAAARecord {
         (Ipv6Addr::from(address))
     ));
 
-    pub fn new(rr: &ResourceRecord) -> AAAARecord {
-        let (_, address) = Self::parse_rdata(rr.rdata).unwrap();
-        AAAARecord { address }
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<AAAARecord, DnsParseError> {
+        let (_, address) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(AAAARecord { address })
     }
 }
 
@@ -84,22 +88,20 @@ impl std::fmt::Display for AAAARecord {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct PtrRecord {
-    name: String,
+    pub name: DomainName,
 }
 
 impl PtrRecord {
-    named!(parse_rdata<&[u8], CompressedNameChain>, do_parse!(
+    named!(parse_rdata<&[u8], CompressedNameChain<'_>>, do_parse!(
         name_chain: parse_compressed_chain >>
         (name_chain)
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> PtrRecord {
-        let (_, mut name_chain) = Self::parse_rdata(rr.rdata).unwrap();
-        let name = match name_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_string(),
-            None => String::new(),
-        };
-        PtrRecord { name }
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<PtrRecord, DnsParseError> {
+        let (_, name_chain) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let name = name_chain.resolve_name(full_dns_message)?;
+        Ok(PtrRecord { name })
     }
 }
 
@@ -109,54 +111,107 @@ impl std::fmt::Display for PtrRecord {
     }
 }
 
+fn serialize_lossy_strings<S: Serializer>(
+    strings: &[Vec<u8>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(strings.iter().map(|s| String::from_utf8_lossy(s)))
+}
+
+/// A character string in zone file form (RFC 1035 5.1), bytes that are
+/// not printable ASCII as `\DDD`.
+fn escape_string(string: &[u8]) -> String {
+    let mut out = String::new();
+    for &b in string {
+        match b {
+            b'\\' | b'"' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\{:03}", b);
+            }
+        }
+    }
+    out
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct TxtRecord {
-    len: u8,
-    bytes: Vec<u8>,
-    text: String,
+    #[serde(serialize_with = "serialize_lossy_strings")]
+    pub strings: Vec<Vec<u8>>,
+    /// The strings without loss, escaped as in zone files.
+    pub escaped: Vec<String>,
+    /// All strings concatenated.
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<TxtPayload>,
 }
 
 impl TxtRecord {
-    named!(parse_rdata<&[u8], (u8, &[u8])>, do_parse!(
-        len: be_u8 >>
-        bytes: take!(len) >>
-        ((len, bytes))
-    ));
+    named!(parse_string<&[u8], &[u8]>, length_bytes!(be_u8));
+
+    fn parse_rdata(mut i: &[u8]) -> IResult<&[u8], Vec<&[u8]>> {
+        let mut strings = vec![];
+        loop {
+            let (rest, string) = Self::parse_string(i)?;
+            strings.push(string);
+            i = rest;
+            if i.is_empty() {
+                return Ok((i, strings));
+            }
+        }
+    }
 
-    pub fn new(rr: &ResourceRecord) -> TxtRecord {
-        let (_, (len, bytes)) = Self::parse_rdata(rr.rdata).unwrap();
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<TxtRecord, DnsParseError> {
+        let (_, strings) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(TxtRecord::from_strings(
+            strings.into_iter().map(|s| s.to_vec()).collect(),
+        ))
+    }
+
+    pub fn from_strings(strings: Vec<Vec<u8>>) -> TxtRecord {
+        let text = String::from_utf8_lossy(&strings.concat()).to_string();
+        let payload = TxtPayload::classify(&text);
         TxtRecord {
-            len,
-            bytes: bytes.to_vec(),
-            text: String::from_utf8_lossy(bytes).to_string(),
+            escaped: strings.iter().map(|s| escape_string(s)).collect(),
+            strings,
+            text,
+            payload,
         }
     }
 }
 
 impl std::fmt::Display for TxtRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self.text)
+        for (i, string) in self.strings.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:?}", String::from_utf8_lossy(string))?;
+        }
+        Ok(())
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct CNameRecord {
-    name: String,
+    pub name: DomainName,
 }
 
 impl CNameRecord {
-    named!(parse_rdata<&[u8], CompressedNameChain>, do_parse!(
+    named!(parse_rdata<&[u8], CompressedNameChain<'_>>, do_parse!(
         name_chain: parse_compressed_chain >>
         (name_chain)
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> CNameRecord {
-        let (_, mut name_chain) = Self::parse_rdata(rr.rdata).unwrap();
-        let name = match name_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_string(),
-            None => String::new(),
-        };
-        CNameRecord { name }
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<CNameRecord, DnsParseError> {
+        let (_, name_chain) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let name = name_chain.resolve_name(full_dns_message)?;
+        Ok(CNameRecord { name })
     }
 }
 
@@ -168,22 +223,20 @@ impl std::fmt::Display for CNameRecord {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct NsRecord {
-    name: String,
+    pub name: DomainName,
 }
 
 impl NsRecord {
-    named!(parse_rdata<&[u8], CompressedNameChain>, do_parse!(
+    named!(parse_rdata<&[u8], CompressedNameChain<'_>>, do_parse!(
         name_chain: parse_compressed_chain >>
         (name_chain)
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> NsRecord {
-        let (_, mut name_chain) = Self::parse_rdata(rr.rdata).unwrap();
-        let name = match name_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_owned(),
-            None => String::new(),
-        };
-        NsRecord { name }
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<NsRecord, DnsParseError> {
+        let (_, name_chain) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let name = name_chain.resolve_name(full_dns_message)?;
+        Ok(NsRecord { name })
     }
 }
 
@@ -195,14 +248,14 @@ impl std::fmt::Display for NsRecord {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct SrvRecord {
-    prio: u16,
-    weight: u16,
-    port: u16,
-    target: String,
+    pub prio: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: DomainName,
 }
 
 impl SrvRecord {
-    named!(parse_rdata<&[u8], (u16, u16, u16, CompressedNameChain)>, do_parse!(
+    named!(parse_rdata<&[u8], (u16, u16, u16, CompressedNameChain<'_>)>, do_parse!(
         prio: be_u16 >>
         weight: be_u16 >>
         port: be_u16 >>
@@ -210,18 +263,16 @@ impl SrvRecord {
         ((prio, weight, port, target_chain))
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> SrvRecord {
-        let (_, (prio, weight, port, mut target_chain)) = Self::parse_rdata(rr.rdata).unwrap();
-        let target = match target_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_string(),
-            None => String::new(),
-        };
-        SrvRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<SrvRecord, DnsParseError> {
+        let (_, (prio, weight, port, target_chain)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let target = target_chain.resolve_name(full_dns_message)?;
+        Ok(SrvRecord {
             prio,
             weight,
             port,
             target,
-        }
+        })
     }
 }
 
@@ -236,151 +287,670 @@ impl std::fmt::Display for SrvRecord {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct OptionCookie {
-    client_cookie: String,
-    server_cookie: String,
+pub struct SoaRecord {
+    pub mname: DomainName,
+    pub rname: DomainName,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32,
+}
+
+impl SoaRecord {
+    named!(parse_rdata<&[u8], (CompressedNameChain<'_>, CompressedNameChain<'_>, u32, u32, u32, u32, u32)>, do_parse!(
+        mname: parse_compressed_chain >>
+        rname: parse_compressed_chain >>
+        serial: be_u32 >>
+        refresh: be_u32 >>
+        retry: be_u32 >>
+        expire: be_u32 >>
+        minimum: be_u32 >>
+        ((mname,rname,serial,refresh,retry, expire, minimum))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<SoaRecord, DnsParseError> {
+        let (_, (mname_chain, rname_chain, serial, refresh, retry, expire, minimum)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let mname = mname_chain.resolve_name(full_dns_message)?;
+        let rname = rname_chain.resolve_name(full_dns_message)?;
+
+        Ok(SoaRecord {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        })
+    }
+}
+
+impl std::fmt::Display for SoaRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}/{}",
+            self.mname, self.serial, self.refresh, self.retry, self.expire
+        )
+    }
+}
+
+pub(crate) fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
 }
 
+pub(crate) fn serialize_lossy<S: Serializer>(
+    bytes: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&String::from_utf8_lossy(bytes))
+}
+
+/// Certification authority authorization (RFC 8659).
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub enum EdnsOption {
-    COOKIE(OptionCookie),
-    UnknownCode(u16),
-}
-
-pub(crate) fn parse_edns_option(i: &[u8]) -> IResult<&[u8], EdnsOption> {
-    let (i, code) = be_u16(i)?;
-    let (i, len) = be_u16(i)?;
-    let (i, data) = take!(i, len)?;
-
-    match code {
-        10 => {
-            let client_cookie = hex::encode(&data[0..8]);
-            let server_cookie = if data.len() >= 16 {
-                hex::encode(&data[8..16])
-            } else {
-                String::from("<MISSING>")
-            };
-            value!(
-                i,
-                EdnsOption::COOKIE(OptionCookie {
-                    client_cookie,
-                    server_cookie,
-                })
-            )
+pub struct CaaRecord {
+    pub flags: u8,
+    pub tag: String,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub value: Vec<u8>,
+}
+
+impl CaaRecord {
+    named!(parse_rdata<&[u8], (u8, &[u8], &[u8])>, do_parse!(
+        flags: be_u8 >>
+        tag: length_bytes!(be_u8) >>
+        value: rest >>
+        ((flags, tag, value))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<CaaRecord, DnsParseError> {
+        let (_, (flags, tag, value)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        if tag.is_empty() || !tag.iter().all(u8::is_ascii_alphanumeric) {
+            return Err(rr.invalid_rdata(full_dns_message));
         }
-        _ => value!(i, EdnsOption::UnknownCode(code)),
+        Ok(CaaRecord {
+            flags,
+            tag: String::from_utf8_lossy(tag).to_string(),
+            value: value.to_vec(),
+        })
+    }
+
+    /// The issuer critical flag.
+    pub fn critical(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+}
+
+impl std::fmt::Display for CaaRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {:?}",
+            self.flags,
+            self.tag,
+            String::from_utf8_lossy(&self.value)
+        )
     }
 }
 
+/// TLSA (RFC 6698) and SMIMEA (RFC 8162) certificate associations.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct OptRecord {
-    udp_payload_size: u16,
-    e_rcode: u8,
-    version: u8,
-    opt_do: u8,
-    z: u16,
-    options: Vec<EdnsOption>,
-}
-
-impl OptRecord {
-    pub fn new(rr: &ResourceRecord, _full_dns_message: &[u8]) -> OptRecord {
-        let udp_payload_size = match rr.rrclass {
-            DnsClass::UnknownClass(udp_payload_size) => udp_payload_size,
-            _ => 0,
-        };
-        let e_rcode = ((rr.ttl >> 24) & 0xff) as u8;
-        let version = ((rr.ttl >> 16) & 0xff) as u8;
-        let opt_do = ((rr.ttl >> 15) & 1) as u8;
-        let z = (rr.ttl & 0x7fff) as u16;
-
-        let mut options = Vec::new();
-        let mut rest = rr.rdata;
-        for _ in 0..20 {
-            match parse_edns_option(rest) {
-                Ok((r, option)) => {
-                    options.push(option);
-                    if r.is_empty() {
-                        break;
-                    }
-                    rest = r;
-                }
-                Err(_) => {
-                    break;
-                }
-            }
+pub struct TlsaRecord {
+    pub usage: u8,
+    pub selector: u8,
+    pub matching_type: u8,
+    #[serde(serialize_with = "serialize_hex")]
+    pub data: Vec<u8>,
+}
+
+impl TlsaRecord {
+    named!(parse_rdata<&[u8], (u8, u8, u8, &[u8])>, do_parse!(
+        usage: be_u8 >>
+        selector: be_u8 >>
+        matching_type: be_u8 >>
+        data: rest >>
+        ((usage, selector, matching_type, data))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<TlsaRecord, DnsParseError> {
+        let (_, (usage, selector, matching_type, data)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(TlsaRecord {
+            usage,
+            selector,
+            matching_type,
+            data: data.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for TlsaRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.usage,
+            self.selector,
+            self.matching_type,
+            hex::encode_upper(&self.data)
+        )
+    }
+}
+
+/// SSH host key fingerprint (RFC 4255).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SshfpRecord {
+    pub algorithm: u8,
+    pub fingerprint_type: u8,
+    #[serde(serialize_with = "serialize_hex")]
+    pub fingerprint: Vec<u8>,
+}
+
+impl SshfpRecord {
+    named!(parse_rdata<&[u8], (u8, u8, &[u8])>, do_parse!(
+        algorithm: be_u8 >>
+        fingerprint_type: be_u8 >>
+        fingerprint: rest >>
+        ((algorithm, fingerprint_type, fingerprint))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<SshfpRecord, DnsParseError> {
+        let (_, (algorithm, fingerprint_type, fingerprint)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(SshfpRecord {
+            algorithm,
+            fingerprint_type,
+            fingerprint: fingerprint.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for SshfpRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.algorithm,
+            self.fingerprint_type,
+            hex::encode_upper(&self.fingerprint)
+        )
+    }
+}
+
+/// OpenPGP public key (RFC 7929).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OpenpgpkeyRecord {
+    #[serde(serialize_with = "serialize_base64")]
+    pub public_key: Vec<u8>,
+}
+
+impl OpenpgpkeyRecord {
+    pub fn new(rr: &ResourceRecord) -> OpenpgpkeyRecord {
+        OpenpgpkeyRecord {
+            public_key: rr.rdata.to_vec(),
         }
+    }
+}
 
-        OptRecord {
-            udp_payload_size,
-            e_rcode,
-            version,
-            opt_do,
-            z,
-            options,
+impl std::fmt::Display for OpenpgpkeyRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", BASE64.encode(&self.public_key))
+    }
+}
+
+/// Certificate or CRL (RFC 4398).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CertRecord {
+    pub cert_type: u16,
+    pub key_tag: u16,
+    pub algorithm: u8,
+    #[serde(serialize_with = "serialize_base64")]
+    pub certificate: Vec<u8>,
+}
+
+impl CertRecord {
+    named!(parse_rdata<&[u8], (u16, u16, u8, &[u8])>, do_parse!(
+        cert_type: be_u16 >>
+        key_tag: be_u16 >>
+        algorithm: be_u8 >>
+        certificate: rest >>
+        ((cert_type, key_tag, algorithm, certificate))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<CertRecord, DnsParseError> {
+        let (_, (cert_type, key_tag, algorithm, certificate)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(CertRecord {
+            cert_type,
+            key_tag,
+            algorithm,
+            certificate: certificate.to_vec(),
+        })
+    }
+
+    /// The certificate type mnemonic, if it has one.
+    pub fn cert_type_mnemonic(&self) -> Option<&'static str> {
+        match self.cert_type {
+            1 => Some("PKIX"),
+            2 => Some("SPKI"),
+            3 => Some("PGP"),
+            4 => Some("IPKIX"),
+            5 => Some("ISPKI"),
+            6 => Some("IPGP"),
+            7 => Some("ACPKIX"),
+            8 => Some("IACPKIX"),
+            253 => Some("URI"),
+            254 => Some("OID"),
+            _ => None,
         }
     }
 }
 
-impl std::fmt::Display for OptRecord {
+impl std::fmt::Display for CertRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "<ROOT>")
+        match self.cert_type_mnemonic() {
+            Some(mnemonic) => write!(f, "{}", mnemonic)?,
+            None => write!(f, "{}", self.cert_type)?,
+        }
+        write!(
+            f,
+            " {} {} {}",
+            self.key_tag,
+            self.algorithm,
+            BASE64.encode(&self.certificate)
+        )
     }
 }
 
+/// Naming authority pointer (RFC 3403).
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct SoaRecord {
-    mname: String,
-    rname: String,
-    serial: u32,
-    refresh: u32,
-    retry: u32,
-    expire: u32,
+pub struct NaptrRecord {
+    pub order: u16,
+    pub preference: u16,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub flags: Vec<u8>,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub services: Vec<u8>,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub regexp: Vec<u8>,
+    pub replacement: DomainName,
 }
 
-impl SoaRecord {
-    named!(parse_rdata<&[u8], (CompressedNameChain, CompressedNameChain, u32, u32, u32, u32)>, do_parse!(
-        mname: parse_compressed_chain >>
-        rname: parse_compressed_chain >>
-        serial: be_u32 >>
-        refresh: be_u32 >>
-        retry: be_u32 >>
-        expire: be_u32 >>
-        ((mname,rname,serial,refresh,retry, expire))
+impl NaptrRecord {
+    #[rustfmt::skip]
+    named!(parse_rdata<&[u8], (u16, u16, &[u8], &[u8], &[u8], CompressedNameChain<'_>)>, do_parse!(
+        order: be_u16 >>
+        preference: be_u16 >>
+        flags: length_bytes!(be_u8) >>
+        services: length_bytes!(be_u8) >>
+        regexp: length_bytes!(be_u8) >>
+        replacement: parse_compressed_chain >>
+        ((order, preference, flags, services, regexp, replacement))
     ));
 
-    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> SoaRecord {
-        let (_, (mut mname_chain, mut rname_chain, serial, refresh, retry, expire)) =
-            Self::parse_rdata(rr.rdata).unwrap();
-        let mname = match mname_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_string(),
-            None => String::new(),
-        };
-        let rname = match rname_chain.resolve_name(0, full_dns_message) {
-            Some(n) => n.to_string(),
-            None => String::new(),
-        };
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<NaptrRecord, DnsParseError> {
+        let (_, (order, preference, flags, services, regexp, replacement_chain)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let replacement = replacement_chain.resolve_name(full_dns_message)?;
+        Ok(NaptrRecord {
+            order,
+            preference,
+            flags: flags.to_vec(),
+            services: services.to_vec(),
+            regexp: regexp.to_vec(),
+            replacement,
+        })
+    }
+}
 
-        SoaRecord {
-            mname,
-            rname,
-            serial,
-            refresh,
-            retry,
-            expire,
+impl std::fmt::Display for NaptrRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {:?} {:?} {:?} {}",
+            self.order,
+            self.preference,
+            String::from_utf8_lossy(&self.flags),
+            String::from_utf8_lossy(&self.services),
+            String::from_utf8_lossy(&self.regexp),
+            self.replacement
+        )
+    }
+}
+
+/// Uniform resource identifier (RFC 7553).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UriRecord {
+    pub priority: u16,
+    pub weight: u16,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub target: Vec<u8>,
+}
+
+impl UriRecord {
+    named!(parse_rdata<&[u8], (u16, u16, &[u8])>, do_parse!(
+        priority: be_u16 >>
+        weight: be_u16 >>
+        target: rest >>
+        ((priority, weight, target))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<UriRecord, DnsParseError> {
+        let (_, (priority, weight, target)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(UriRecord {
+            priority,
+            weight,
+            target: target.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for UriRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {:?}",
+            self.priority,
+            self.weight,
+            String::from_utf8_lossy(&self.target)
+        )
+    }
+}
+
+/// Geographic location (RFC 1876). Coordinates are kept in wire format:
+/// thousandths of an arc second offset by 2^31, and centimeters above
+/// 100000m below the WGS 84 spheroid.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LocRecord {
+    pub version: u8,
+    pub size: u8,
+    pub horiz_pre: u8,
+    pub vert_pre: u8,
+    pub latitude: u32,
+    pub longitude: u32,
+    pub altitude: u32,
+}
+
+impl LocRecord {
+    #[rustfmt::skip]
+    named!(parse_rdata<&[u8], (u8, u8, u8, u8, u32, u32, u32)>, do_parse!(
+        version: be_u8 >>
+        size: be_u8 >>
+        horiz_pre: be_u8 >>
+        vert_pre: be_u8 >>
+        latitude: be_u32 >>
+        longitude: be_u32 >>
+        altitude: be_u32 >>
+        ((version, size, horiz_pre, vert_pre, latitude, longitude, altitude))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<LocRecord, DnsParseError> {
+        let (_, (version, size, horiz_pre, vert_pre, latitude, longitude, altitude)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let valid_precision = |p: u8| p >> 4 <= 9 && p & 0xf <= 9;
+        if version != 0
+            || ![size, horiz_pre, vert_pre]
+                .iter()
+                .all(|p| valid_precision(*p))
+        {
+            return Err(rr.invalid_rdata(full_dns_message));
         }
+        Ok(LocRecord {
+            version,
+            size,
+            horiz_pre,
+            vert_pre,
+            latitude,
+            longitude,
+            altitude,
+        })
+    }
+
+    /// A size or precision field in centimeters.
+    pub fn precision_cm(precision: u8) -> u64 {
+        u64::from(precision >> 4) * 10u64.pow(u32::from(precision & 0xf))
+    }
+
+    /// Altitude in centimeters relative to the WGS 84 spheroid.
+    pub fn altitude_cm(&self) -> i64 {
+        i64::from(self.altitude) - 10_000_000
     }
 }
 
-impl std::fmt::Display for SoaRecord {
+fn fmt_loc_angle(
+    f: &mut std::fmt::Formatter,
+    angle: u32,
+    positive: char,
+    negative: char,
+) -> std::fmt::Result {
+    let value = i64::from(angle) - (1 << 31);
+    let hemisphere = if value < 0 { negative } else { positive };
+    let value = value.abs();
+    write!(
+        f,
+        "{} {} {}.{:03} {}",
+        value / 3_600_000,
+        value / 60_000 % 60,
+        value / 1000 % 60,
+        value % 1000,
+        hemisphere
+    )
+}
+
+fn fmt_loc_precision(f: &mut std::fmt::Formatter, precision: u8) -> std::fmt::Result {
+    let cm = LocRecord::precision_cm(precision);
+    if cm % 100 == 0 {
+        write!(f, "{}m", cm / 100)
+    } else {
+        write!(f, "{}.{:02}m", cm / 100, cm % 100)
+    }
+}
+
+impl std::fmt::Display for LocRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt_loc_angle(f, self.latitude, 'N', 'S')?;
+        write!(f, " ")?;
+        fmt_loc_angle(f, self.longitude, 'E', 'W')?;
+        let altitude = self.altitude_cm();
+        let sign = if altitude < 0 { "-" } else { "" };
+        let altitude = altitude.abs();
+        write!(f, " {}{}.{:02}m", sign, altitude / 100, altitude % 100)?;
+        for precision in [self.size, self.horiz_pre, self.vert_pre] {
+            write!(f, " ")?;
+            fmt_loc_precision(f, precision)?;
+        }
+        Ok(())
+    }
+}
+
+/// Host information (RFC 1035 3.3.2).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HinfoRecord {
+    #[serde(serialize_with = "serialize_lossy")]
+    pub cpu: Vec<u8>,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub os: Vec<u8>,
+}
+
+impl HinfoRecord {
+    named!(parse_rdata<&[u8], (&[u8], &[u8])>, do_parse!(
+        cpu: length_bytes!(be_u8) >>
+        os: length_bytes!(be_u8) >>
+        ((cpu, os))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<HinfoRecord, DnsParseError> {
+        let (_, (cpu, os)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        Ok(HinfoRecord {
+            cpu: cpu.to_vec(),
+            os: os.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Display for HinfoRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "{}/{}/{}/{}/{}",
-            self.mname, self.serial, self.refresh, self.retry, self.expire
+            "{:?} {:?}",
+            String::from_utf8_lossy(&self.cpu),
+            String::from_utf8_lossy(&self.os)
         )
     }
 }
 
+/// Responsible person (RFC 1183).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RpRecord {
+    pub mbox: DomainName,
+    pub txt: DomainName,
+}
+
+impl RpRecord {
+    named!(parse_rdata<&[u8], (CompressedNameChain<'_>, CompressedNameChain<'_>)>, do_parse!(
+        mbox: parse_compressed_chain >>
+        txt: parse_compressed_chain >>
+        ((mbox, txt))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<RpRecord, DnsParseError> {
+        let (_, (mbox_chain, txt_chain)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let mbox = mbox_chain.resolve_name(full_dns_message)?;
+        let txt = txt_chain.resolve_name(full_dns_message)?;
+        Ok(RpRecord { mbox, txt })
+    }
+}
+
+impl std::fmt::Display for RpRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.mbox, self.txt)
+    }
+}
+
+/// AFS database location (RFC 1183).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AfsdbRecord {
+    pub subtype: u16,
+    pub hostname: DomainName,
+}
+
+impl AfsdbRecord {
+    named!(parse_rdata<&[u8], (u16, CompressedNameChain<'_>)>, do_parse!(
+        subtype: be_u16 >>
+        hostname: parse_compressed_chain >>
+        ((subtype, hostname))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<AfsdbRecord, DnsParseError> {
+        let (_, (subtype, hostname_chain)) =
+            Self::parse_rdata(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let hostname = hostname_chain.resolve_name(full_dns_message)?;
+        Ok(AfsdbRecord { subtype, hostname })
+    }
+}
+
+impl std::fmt::Display for AfsdbRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.subtype, self.hostname)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DnameRecord {
+    pub target: DomainName,
+}
+
+impl DnameRecord {
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<DnameRecord, DnsParseError> {
+        let (_, target_chain) =
+            parse_compressed_chain(&rr.rdata).map_err(|_| rr.invalid_rdata(full_dns_message))?;
+        let target = target_chain.resolve_name(full_dns_message)?;
+        Ok(DnameRecord { target })
+    }
+}
+
+impl std::fmt::Display for DnameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.target)
+    }
+}
+
+/// NULL rdata (RFC 1035 3.3.10) is arbitrary bytes, so it is shown in
+/// the generic format.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NullRecord {
+    #[serde(serialize_with = "serialize_hex")]
+    pub data: Vec<u8>,
+}
+
+impl NullRecord {
+    pub fn new(rr: &ResourceRecord) -> NullRecord {
+        NullRecord {
+            data: rr.rdata.to_vec(),
+        }
+    }
+}
+
+impl std::fmt::Display for NullRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "\\# {}", self.data.len())?;
+        if !self.data.is_empty() {
+            write!(f, " {}", hex::encode(&self.data))?;
+        }
+        Ok(())
+    }
+}
+
+/// Undecoded rdata, kept for record types without a parser.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RawRecord {
+    pub rrtype: u16,
+    pub rdlength: u16,
+    #[serde(serialize_with = "serialize_hex")]
+    pub rdata: Vec<u8>,
+}
+
+impl RawRecord {
+    pub fn new(rr: &ResourceRecord) -> RawRecord {
+        RawRecord {
+            rrtype: u16::from(&rr.rrtype),
+            rdlength: rr.rdlength,
+            rdata: rr.rdata.to_vec(),
+        }
+    }
+}
+
+/// RFC 3597 generic rdata format: `\# <length> <hex>`.
+impl std::fmt::Display for RawRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "\\# {}", self.rdlength)?;
+        if !self.rdata.is_empty() {
+            write!(f, " {}", hex::encode(&self.rdata))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rdata that could not be decoded as its record type.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MalformedRecord {
+    #[serde(flatten)]
+    pub raw: RawRecord,
+    pub error: DnsParseError,
+}
+
+impl std::fmt::Display for MalformedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} <{}>", self.raw, self.error)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub enum RRecordTypes {
     MX(MxRecord),
@@ -393,7 +963,32 @@ pub enum RRecordTypes {
     SRV(SrvRecord),
     SOA(SoaRecord),
     OPT(OptRecord),
-    ParserNotImpl,
+    DNSKEY(DnskeyRecord),
+    RRSIG(RrsigRecord),
+    DS(DsRecord),
+    NSEC(NsecRecord),
+    NSEC3(Nsec3Record),
+    NSEC3PARAM(Nsec3ParamRecord),
+    SVCB(SvcbRecord),
+    HTTPS(SvcbRecord),
+    CAA(CaaRecord),
+    TLSA(TlsaRecord),
+    SMIMEA(TlsaRecord),
+    SSHFP(SshfpRecord),
+    OPENPGPKEY(OpenpgpkeyRecord),
+    CERT(CertRecord),
+    NAPTR(NaptrRecord),
+    URI(UriRecord),
+    LOC(LocRecord),
+    HINFO(HinfoRecord),
+    RP(RpRecord),
+    AFSDB(AfsdbRecord),
+    DNAME(DnameRecord),
+    NULL(NullRecord),
+    NB(NbRecord),
+    NBSTAT(NbstatRecord),
+    Raw(RawRecord),
+    Malformed(MalformedRecord),
 }
 
 impl std::fmt::Display for RRecordTypes {
@@ -409,96 +1004,341 @@ impl std::fmt::Display for RRecordTypes {
             RRecordTypes::SRV(v) => write!(f, "{}", v),
             RRecordTypes::SOA(v) => write!(f, "{}", v),
             RRecordTypes::OPT(v) => write!(f, "{}", v),
-            RRecordTypes::ParserNotImpl => write!(f, "ParserNotImpl"),
+            RRecordTypes::DNSKEY(v) => write!(f, "{}", v),
+            RRecordTypes::RRSIG(v) => write!(f, "{}", v),
+            RRecordTypes::DS(v) => write!(f, "{}", v),
+            RRecordTypes::NSEC(v) => write!(f, "{}", v),
+            RRecordTypes::NSEC3(v) => write!(f, "{}", v),
+            RRecordTypes::NSEC3PARAM(v) => write!(f, "{}", v),
+            RRecordTypes::SVCB(v) => write!(f, "{}", v),
+            RRecordTypes::HTTPS(v) => write!(f, "{}", v),
+            RRecordTypes::CAA(v) => write!(f, "{}", v),
+            RRecordTypes::TLSA(v) => write!(f, "{}", v),
+            RRecordTypes::SMIMEA(v) => write!(f, "{}", v),
+            RRecordTypes::SSHFP(v) => write!(f, "{}", v),
+            RRecordTypes::OPENPGPKEY(v) => write!(f, "{}", v),
+            RRecordTypes::CERT(v) => write!(f, "{}", v),
+            RRecordTypes::NAPTR(v) => write!(f, "{}", v),
+            RRecordTypes::URI(v) => write!(f, "{}", v),
+            RRecordTypes::LOC(v) => write!(f, "{}", v),
+            RRecordTypes::HINFO(v) => write!(f, "{}", v),
+            RRecordTypes::RP(v) => write!(f, "{}", v),
+            RRecordTypes::AFSDB(v) => write!(f, "{}", v),
+            RRecordTypes::DNAME(v) => write!(f, "{}", v),
+            RRecordTypes::NULL(v) => write!(f, "{}", v),
+            RRecordTypes::NB(v) => write!(f, "{}", v),
+            RRecordTypes::NBSTAT(v) => write!(f, "{}", v),
+            RRecordTypes::Raw(v) => write!(f, "{}", v),
+            RRecordTypes::Malformed(v) => write!(f, "{}", v),
             // _ => write!(f, "RRtodo()"),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl RRecordTypes {
+    /// The record type the rdata belongs to.
+    pub fn rrtype(&self) -> DnsType {
+        match self {
+            RRecordTypes::MX(_) => DnsType::MX,
+            RRecordTypes::A(_) => DnsType::A,
+            RRecordTypes::AAAA(_) => DnsType::AAAA,
+            RRecordTypes::PTR(_) => DnsType::PTR,
+            RRecordTypes::TXT(_) => DnsType::TXT,
+            RRecordTypes::CNAME(_) => DnsType::CNAME,
+            RRecordTypes::NS(_) => DnsType::NS,
+            RRecordTypes::SRV(_) => DnsType::SRV,
+            RRecordTypes::SOA(_) => DnsType::SOA,
+            RRecordTypes::OPT(_) => DnsType::OPT,
+            RRecordTypes::DNSKEY(_) => DnsType::DNSKEY,
+            RRecordTypes::RRSIG(_) => DnsType::RRSIG,
+            RRecordTypes::DS(_) => DnsType::DS,
+            RRecordTypes::NSEC(_) => DnsType::NSEC,
+            RRecordTypes::NSEC3(_) => DnsType::NSEC3,
+            RRecordTypes::NSEC3PARAM(_) => DnsType::NSEC3PARAM,
+            RRecordTypes::SVCB(_) => DnsType::SVCB,
+            RRecordTypes::HTTPS(_) => DnsType::HTTPS,
+            RRecordTypes::CAA(_) => DnsType::CAA,
+            RRecordTypes::TLSA(_) => DnsType::TLSA,
+            RRecordTypes::SMIMEA(_) => DnsType::SMIMEA,
+            RRecordTypes::SSHFP(_) => DnsType::SSHFP,
+            RRecordTypes::OPENPGPKEY(_) => DnsType::OPENPGPKEY,
+            RRecordTypes::CERT(_) => DnsType::CERT,
+            RRecordTypes::NAPTR(_) => DnsType::NAPTR,
+            RRecordTypes::URI(_) => DnsType::URI,
+            RRecordTypes::LOC(_) => DnsType::LOC,
+            RRecordTypes::HINFO(_) => DnsType::HINFO,
+            RRecordTypes::RP(_) => DnsType::RP,
+            RRecordTypes::AFSDB(_) => DnsType::AFSDB,
+            RRecordTypes::DNAME(_) => DnsType::DNAME,
+            RRecordTypes::NULL(_) => DnsType::NULL,
+            RRecordTypes::NB(_) => DnsType::from(NBNS_NB),
+            RRecordTypes::NBSTAT(_) => DnsType::from(NBNS_NBSTAT),
+            RRecordTypes::Raw(v) => DnsType::from(v.rrtype),
+            RRecordTypes::Malformed(v) => DnsType::from(v.raw.rrtype),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct ResourceRecord<'a> {
-    pub name_chain: CompressedNameChain<'a>,
+    pub name: DomainName,
     pub rrtype: DnsType,
     pub rrclass: DnsClass,
+    /// The mDNS cache-flush bit (RFC 6762 10.2).
+    pub cache_flush: bool,
     pub ttl: u32,
     pub rdlength: u16,
-    pub rdata: &'a [u8],
+    pub rdata: Cow<'a, [u8]>,
     pub record: Option<RRecordTypes>,
 }
 
+impl ResourceRecord<'static> {
+    /// A record for building messages. The wire rdata is generated from
+    /// `record`, without name compression.
+    ///
+    /// OPT records take their class and TTL from the `OptRecord` fields
+    /// (RFC 6891 6.1.3), `rrclass` and `ttl` are ignored.
+    pub fn new(
+        name: DomainName,
+        rrclass: DnsClass,
+        ttl: u32,
+        record: RRecordTypes,
+    ) -> ResourceRecord<'static> {
+        let (rrclass, ttl) = match &record {
+            RRecordTypes::OPT(opt) => (DnsClass::OtherUsage(opt.udp_payload_size), opt.ttl()),
+            _ => (rrclass, ttl),
+        };
+        let rdata = encode_rdata(&record);
+        ResourceRecord {
+            name,
+            rrtype: record.rrtype(),
+            rrclass,
+            cache_flush: false,
+            ttl,
+            rdlength: rdata.len() as u16,
+            rdata: Cow::Owned(rdata),
+            record: Some(record),
+        }
+    }
+}
+
 impl<'a> ResourceRecord<'a> {
+    /// Decode the rdata.
+    ///
+    /// Rdata that does not match its type is kept as
+    /// `RRecordTypes::Malformed`, with the error reported as found in the
+    /// header section.
     pub fn resolve(&mut self, full_dns_message: &[u8]) {
-        self.name_chain.resolve_name(0, full_dns_message);
-        match self.record {
-            Some(_) => {}
-            None => match (&self.rrclass, &self.rrtype) {
-                (DnsClass::IN, DnsType::MX) => {
-                    self.record = Some(RRecordTypes::MX(MxRecord::new(self, full_dns_message)));
-                }
-                (DnsClass::IN, DnsType::A) => {
-                    self.record = Some(RRecordTypes::A(ARecord::new(self)));
-                }
-                (DnsClass::IN, DnsType::AAAA) => {
-                    self.record = Some(RRecordTypes::AAAA(AAAARecord::new(self)));
-                }
-                (DnsClass::IN, DnsType::PTR) => {
-                    self.record = Some(RRecordTypes::PTR(PtrRecord::new(self, full_dns_message)));
-                }
-                (DnsClass::IN, DnsType::TXT) => {
-                    self.record = Some(RRecordTypes::TXT(TxtRecord::new(self)));
-                }
-                (DnsClass::IN, DnsType::CNAME) => {
-                    self.record = Some(RRecordTypes::CNAME(CNameRecord::new(
-                        self,
-                        full_dns_message,
-                    )));
-                }
-                (DnsClass::IN, DnsType::NS) => {
-                    self.record = Some(RRecordTypes::NS(NsRecord::new(self, full_dns_message)));
-                }
-                (DnsClass::IN, DnsType::SRV) => {
-                    self.record = Some(RRecordTypes::SRV(SrvRecord::new(self, full_dns_message)));
-                }
-                (DnsClass::IN, DnsType::SOA) => {
-                    self.record = Some(RRecordTypes::SOA(SoaRecord::new(self, full_dns_message)));
-                }
-                (DnsClass::UnknownClass(len), DnsType::OPT) => {
-                    self.record = Some(RRecordTypes::OPT(OptRecord::new(
-                        self,
-                        full_dns_message,
-                    )));
-                    self.rrclass = DnsClass::OtherUsage(*len);
-                }
-                // (_, _) => self.record = Some(RRecordTypes::ParserNotImpl),
-                (cc, tt) => {
-                    self.record = Some(RRecordTypes::ParserNotImpl);
-                    debug!(
-                        "No parser for RRecord(DnsClass::{:?}, DnsType::{:?}) - rdata: {:02x?}",
-                        cc, tt, self.rdata
-                    );
-                }
-            },
+        self.resolve_at(full_dns_message, Section::Header, 0)
+    }
+
+    pub(crate) fn resolve_at(&mut self, full_dns_message: &[u8], section: Section, index: usize) {
+        if self.record.is_some() {
+            return;
+        }
+        let record = self.decode_rdata(full_dns_message).unwrap_or_else(|e| {
+            debug!("{} - rdata: {:02x?}", e, self.rdata);
+            RRecordTypes::Malformed(MalformedRecord {
+                raw: RawRecord::new(self),
+                error: e.at(section, index),
+            })
+        });
+        if self.rrtype == DnsType::OPT {
+            self.rrclass = DnsClass::OtherUsage(u16::from(&self.rrclass));
+        }
+        self.record = Some(record);
+    }
+
+    fn decode_rdata(&self, full_dns_message: &[u8]) -> Result<RRecordTypes, DnsParseError> {
+        // UPDATE deletes single records with class NONE and the rdata as
+        // it is in the zone (RFC 2136 2.5.4).
+        let rrclass = match &self.rrclass {
+            DnsClass::NONE if !self.rdata.is_empty() => &DnsClass::IN,
+            rrclass => rrclass,
+        };
+        let record = match (rrclass, &self.rrtype) {
+            (DnsClass::IN, DnsType::MX) => RRecordTypes::MX(MxRecord::new(self, full_dns_message)?),
+            (DnsClass::IN, DnsType::A) => RRecordTypes::A(ARecord::new(self, full_dns_message)?),
+            (DnsClass::IN, DnsType::AAAA) => {
+                RRecordTypes::AAAA(AAAARecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::PTR) => {
+                RRecordTypes::PTR(PtrRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::TXT) => {
+                RRecordTypes::TXT(TxtRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::CNAME) => {
+                RRecordTypes::CNAME(CNameRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::NS) => RRecordTypes::NS(NsRecord::new(self, full_dns_message)?),
+            (DnsClass::IN, DnsType::SRV) => {
+                RRecordTypes::SRV(SrvRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::SOA) => {
+                RRecordTypes::SOA(SoaRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::DNSKEY) => {
+                RRecordTypes::DNSKEY(DnskeyRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::RRSIG) => {
+                RRecordTypes::RRSIG(RrsigRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::DS) => RRecordTypes::DS(DsRecord::new(self, full_dns_message)?),
+            (DnsClass::IN, DnsType::NSEC) => {
+                RRecordTypes::NSEC(NsecRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::NSEC3) => {
+                RRecordTypes::NSEC3(Nsec3Record::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::NSEC3PARAM) => {
+                RRecordTypes::NSEC3PARAM(Nsec3ParamRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::SVCB) => {
+                RRecordTypes::SVCB(SvcbRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::HTTPS) => {
+                RRecordTypes::HTTPS(SvcbRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::CAA) => {
+                RRecordTypes::CAA(CaaRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::TLSA) => {
+                RRecordTypes::TLSA(TlsaRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::SMIMEA) => {
+                RRecordTypes::SMIMEA(TlsaRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::SSHFP) => {
+                RRecordTypes::SSHFP(SshfpRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::OPENPGPKEY) => {
+                RRecordTypes::OPENPGPKEY(OpenpgpkeyRecord::new(self))
+            }
+            (DnsClass::IN, DnsType::CERT) => {
+                RRecordTypes::CERT(CertRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::NAPTR) => {
+                RRecordTypes::NAPTR(NaptrRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::URI) => {
+                RRecordTypes::URI(UriRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::LOC) => {
+                RRecordTypes::LOC(LocRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::HINFO) => {
+                RRecordTypes::HINFO(HinfoRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::RP) => RRecordTypes::RP(RpRecord::new(self, full_dns_message)?),
+            (DnsClass::IN, DnsType::AFSDB) => {
+                RRecordTypes::AFSDB(AfsdbRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::DNAME) => {
+                RRecordTypes::DNAME(DnameRecord::new(self, full_dns_message)?)
+            }
+            (DnsClass::IN, DnsType::NULL) => RRecordTypes::NULL(NullRecord::new(self)),
+            (_, DnsType::OPT) => RRecordTypes::OPT(OptRecord::new(self, full_dns_message)?),
+            (cc, tt) => {
+                debug!(
+                    "No parser for RRecord(DnsClass::{:?}, DnsType::{:?}) - rdata: {:02x?}",
+                    cc, tt, self.rdata
+                );
+                RRecordTypes::Raw(RawRecord::new(self))
+            }
+        };
+        Ok(record)
+    }
+
+    /// Copy the rdata so the record no longer borrows the packet.
+    pub fn into_owned(self) -> ResourceRecord<'static> {
+        ResourceRecord {
+            name: self.name,
+            rrtype: self.rrtype,
+            rrclass: self.rrclass,
+            cache_flush: self.cache_flush,
+            ttl: self.ttl,
+            rdlength: self.rdlength,
+            rdata: Cow::Owned(self.rdata.into_owned()),
+            record: self.record,
         }
     }
+
+    pub(crate) fn invalid_rdata(&self, full_dns_message: &[u8]) -> DnsParseError {
+        let offset = offset_in(full_dns_message, &self.rdata).unwrap_or(0);
+        DnsParseError::invalid_rdata(offset, &self.rrtype)
+    }
 }
 
+/// Records are equal when their decoded rdata is. The wire rdata is only
+/// compared for unresolved records, as name compression changes it.
+impl PartialEq for ResourceRecord<'_> {
+    fn eq(&self, other: &ResourceRecord<'_>) -> bool {
+        let same_rdata = match (&self.record, &other.record) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.rdata == other.rdata,
+            _ => false,
+        };
+        self.name == other.name
+            && self.rrtype == other.rrtype
+            && self.rrclass == other.rrclass
+            && self.cache_flush == other.cache_flush
+            && self.ttl == other.ttl
+            && same_rdata
+    }
+}
+
+impl Eq for ResourceRecord<'_> {}
+
 #[rustfmt::skip]
-named!(pub (crate) parse_resource_record<&[u8], ResourceRecord>, do_parse!(
+named!(pub (crate) parse_resource_record_fields<&[u8], (CompressedNameChain<'_>, DnsType, DnsClass, u32, &[u8])>, do_parse!(
     name_chain: parse_compressed_chain >>
-    rrtype: parse_dnstype >> 
+    rrtype: parse_dnstype >>
     rrclass: parse_dnsclass >>
     ttl: be_u32 >>
     rdlength: be_u16 >>
     rdata: take!(rdlength) >>
-    (
-        ResourceRecord{ name_chain, rrtype, rrclass, ttl, rdlength, rdata, record: None }
-    )
+    ((name_chain, rrtype, rrclass, ttl, rdata))
 ));
 
+/// The class and cache-flush bit of a record. The class of OPT records
+/// is the UDP payload size and is kept whole.
+pub(crate) fn split_record_class(rrtype: &DnsType, rrclass: DnsClass) -> (DnsClass, bool) {
+    match rrtype {
+        DnsType::OPT => (rrclass, false),
+        _ => rrclass.split_mdns_bit(),
+    }
+}
+
+/// Parse the resource record at the start of `i`, a slice of
+/// `full_dns_message`. The rdata is left undecoded.
+pub(crate) fn parse_resource_record<'a>(
+    full_dns_message: &[u8],
+    i: &'a [u8],
+) -> ParseResult<'a, ResourceRecord<'a>> {
+    let offset = offset_in(full_dns_message, i).unwrap_or(0);
+    let (rest, (name_chain, rrtype, rrclass, ttl, rdata)) = parse_resource_record_fields(i)
+        .map_err(|e| DnsParseError::from_nom(e, full_dns_message, offset))?;
+    let name = name_chain.resolve_name(full_dns_message)?;
+    let (rrclass, cache_flush) = split_record_class(&rrtype, rrclass);
+    Ok((
+        rest,
+        ResourceRecord {
+            name,
+            rrtype,
+            rrclass,
+            cache_flush,
+            ttl,
+            rdlength: rdata.len() as u16,
+            rdata: Cow::Borrowed(rdata),
+            record: None,
+        },
+    ))
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    #[allow(unused_imports)]
 
     const DNS_RESPONSE: &[u8] = include_bytes!("../../../fixtures/dns/dns_response1.bin");
 
@@ -506,7 +1346,7 @@ mod tests {
     fn test_parse_resource_record() {
         let (offset, len) = (28, 22);
         let rr1 = &DNS_RESPONSE[offset..offset + len];
-        let (_, rr) = parse_resource_record(rr1).unwrap();
+        let (_, rr) = parse_resource_record(DNS_RESPONSE, rr1).unwrap();
         println!("{:?}", rr);
         assert_eq!(rr.rrtype, DnsType::MX);
         assert_eq!(rr.rrclass, DnsClass::IN);
@@ -552,12 +1392,31 @@ mod tests {
         let rdata: [u8; 16] = [
             15, 118, 61, 115, 112, 102, 49, 32, 112, 116, 114, 32, 63, 97, 108, 108,
         ];
-        let (_, (len, v)) = TxtRecord::parse_rdata(&rdata).unwrap();
-        println!(
-            "TxtRecord::parse_rdata -> len:{}, bytes: {:x?}, string: {:?}",
-            len,
-            v,
-            String::from_utf8_lossy(v)
+        let (_, v) = TxtRecord::parse_rdata(&rdata).unwrap();
+        println!("TxtRecord::parse_rdata -> {:x?}", v);
+        assert_eq!(vec![&rdata[1..]], v);
+    }
+
+    #[test]
+    fn test_txt_record_strings() {
+        let record = resolved(16, b"\x0cv=spf1 ip4:1\x0d92.0.2.1 -all\x00");
+        assert_eq!(r#""v=spf1 ip4:1" "92.0.2.1 -all" """#, record.to_string());
+        let txt = match &record {
+            RRecordTypes::TXT(txt) => txt,
+            _ => panic!("{:?}", record),
+        };
+        assert_eq!(3, txt.strings.len());
+        assert_eq!("v=spf1 ip4:192.0.2.1 -all", txt.text);
+        assert!(matches!(txt.payload, Some(TxtPayload::Spf(_))));
+        match resolved(16, b"") {
+            RRecordTypes::Malformed(_) => (),
+            record => panic!("{:?}", record),
+        }
+
+        let record = resolved(16, b"\x06a\xff\"\\\n ");
+        assert_eq!(
+            r#"{"TXT":{"strings":["a�\"\\\n "],"escaped":["a\\255\\\"\\\\\\010 "],"text":"a�\"\\\n "}}"#,
+            serde_json::to_string(&record).unwrap()
         );
     }
 
@@ -595,29 +1454,168 @@ mod tests {
     }
 
     #[test]
-    fn test_edns_option_parser() {
-        let rdata: [u8; 12] = [
-            0x00, 0x0a, 0x00, 0x08, 0x8e, 0xa0, 0xf3, 0xd3, 0x6b, 0x19, 0x5c, 0xf7,
-        ];
+    fn test_short_rdata_is_malformed() {
+        let data: [u8; 13] = [0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 2, 10, 0];
+        let (_, mut rr) = parse_resource_record(&data, &data).unwrap();
+        rr.resolve(&data);
+        let malformed = MalformedRecord {
+            raw: RawRecord {
+                rrtype: 1,
+                rdlength: 2,
+                rdata: vec![10, 0],
+            },
+            error: DnsParseError::invalid_rdata(11, &DnsType::A),
+        };
+        assert_eq!(Some(RRecordTypes::Malformed(malformed)), rr.record);
+        let record = rr.record.unwrap();
+        assert!(record
+            .to_string()
+            .starts_with("\\# 2 0a00 <invalid A rdata"));
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(
+            json.starts_with(r#"{"Malformed":{"rrtype":1,"rdlength":2,"rdata":"0a00","error":"#)
+        );
+    }
+
+    #[test]
+    fn test_unknown_type_is_raw() {
+        let data: [u8; 15] = [0, 0, 99, 0, 1, 0, 0, 0, 60, 0, 4, 0xde, 0xad, 0xbe, 0xef];
+        let (_, mut rr) = parse_resource_record(&data, &data).unwrap();
+        rr.resolve(&data);
+        let record = rr.record.unwrap();
+        assert_eq!("\\# 4 deadbeef", record.to_string());
+        assert_eq!(
+            r#"{"Raw":{"rrtype":99,"rdlength":4,"rdata":"deadbeef"}}"#,
+            serde_json::to_string(&record).unwrap()
+        );
+    }
 
-        let (r, edns_option) = parse_edns_option(&rdata).unwrap();
-        println!("{:?}", edns_option);
-        assert!(r.is_empty());
+    /// The record of type `rrtype` with `rdata`, parsed and resolved.
+    pub(crate) fn resolved(rrtype: u16, rdata: &[u8]) -> RRecordTypes {
+        let mut data = vec![0, (rrtype >> 8) as u8, rrtype as u8, 0, 1, 0, 0, 0, 60];
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(rdata);
+        let (_, mut rr) = parse_resource_record(&data, &data).unwrap();
+        rr.resolve(&data);
+        rr.record.unwrap()
     }
 
     #[test]
-    fn test_opt_record_parser() {
-        let rdata: [u8; 23] = [
-            0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0a, 0x00,
-            0x08, 0x8e, 0xa0, 0xf3, 0xd3, 0x6b, 0x19, 0x5c, 0xf7,
-        ];
+    fn test_caa_record() {
+        let record = resolved(257, b"\x80\x05issueletsencrypt.org");
+        match &record {
+            RRecordTypes::CAA(caa) => assert!(caa.critical()),
+            _ => panic!("{:?}", record),
+        }
+        assert_eq!("128 issue \"letsencrypt.org\"", record.to_string());
+        assert_eq!(
+            r#"{"CAA":{"flags":128,"tag":"issue","value":"letsencrypt.org"}}"#,
+            serde_json::to_string(&record).unwrap()
+        );
+        match resolved(257, b"\x00\x00") {
+            RRecordTypes::Malformed(_) => (),
+            record => panic!("{:?}", record),
+        }
+    }
 
-        let (_, rr) = parse_resource_record(&rdata).unwrap();
-        assert_eq!(rr.rrtype, DnsType::OPT);
+    #[test]
+    fn test_certificate_association_records() {
+        let tlsa = resolved(52, &[3, 1, 1, 0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!("3 1 1 DEADBEEF", tlsa.to_string());
+        assert_eq!(
+            r#"{"TLSA":{"usage":3,"selector":1,"matching_type":1,"data":"deadbeef"}}"#,
+            serde_json::to_string(&tlsa).unwrap()
+        );
+        let smimea = resolved(53, &[0, 0, 1, 0xab]);
+        assert_eq!(DnsType::SMIMEA, smimea.rrtype());
+        assert_eq!("0 0 1 AB", smimea.to_string());
+        let sshfp = resolved(44, &[4, 2, 0x12, 0x34]);
+        assert_eq!("4 2 1234", sshfp.to_string());
+        assert_eq!("AQID", resolved(61, &[1, 2, 3]).to_string());
+        let cert = resolved(37, &[0, 1, 0x30, 0x39, 8, 1, 2, 3]);
+        assert_eq!("PKIX 12345 8 AQID", cert.to_string());
+        assert_eq!(
+            r#"{"CERT":{"cert_type":1,"key_tag":12345,"algorithm":8,"certificate":"AQID"}}"#,
+            serde_json::to_string(&cert).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_loc_record() {
+        let mut rdata = vec![0, 0x12, 0x16, 0x13];
+        rdata.extend_from_slice(&2_336_026_648u32.to_be_bytes());
+        rdata.extend_from_slice(&(2_147_483_648u32 - 17_612_500).to_be_bytes());
+        rdata.extend_from_slice(&9_999_800u32.to_be_bytes());
+        let record = resolved(29, &rdata);
+        assert_eq!(
+            "52 22 23.000 N 4 53 32.500 W -2.00m 1m 10000m 10m",
+            record.to_string()
+        );
+        rdata[1] = 0x1a;
+        match resolved(29, &rdata) {
+            RRecordTypes::Malformed(_) => (),
+            record => panic!("{:?}", record),
+        }
+    }
+
+    #[test]
+    fn test_legacy_records() {
+        let mut rdata = vec![0, 100, 0, 10, 1, b'S', 7];
+        rdata.extend_from_slice(b"SIP+D2U\x00\x04_sip\x04_udp\x07example\x00");
+        let naptr = resolved(35, &rdata);
+        assert_eq!(
+            "100 10 \"S\" \"SIP+D2U\" \"\" _sip._udp.example",
+            naptr.to_string()
+        );
+        let uri = resolved(256, b"\x00\x0a\x00\x01https://example.com/");
+        assert_eq!("10 1 \"https://example.com/\"", uri.to_string());
+        let hinfo = resolved(13, b"\x07RFC8482\x00");
+        assert_eq!("\"RFC8482\" \"\"", hinfo.to_string());
+        let afsdb = resolved(18, b"\x00\x01\x03afs\x00");
+        assert_eq!("1 afs", afsdb.to_string());
+        let null = resolved(10, b"\x01\x02");
+        assert_eq!(DnsType::NULL, null.rrtype());
+        assert_eq!("\\# 2 0102", null.to_string());
+        assert_eq!(
+            r#"{"NULL":{"data":"0102"}}"#,
+            serde_json::to_string(&null).unwrap()
+        );
+    }
 
-        let v = OptRecord::new(&rr, &rdata);
-        println!("{:#?}", v);
-        assert_eq!(v.udp_payload_size, 4096);
+    #[test]
+    fn test_rp_record() {
+        // The rdata starts at offset 11, the txt name points at `example`.
+        let rp = resolved(17, b"\x05admin\x07example\x00\xc0\x11");
+        match &rp {
+            RRecordTypes::RP(rp) => assert_eq!("example", rp.txt.to_string()),
+            _ => panic!("{:?}", rp),
+        }
+        assert_eq!("admin.example example", rp.to_string());
+        assert_eq!(
+            r#"{"RP":{"mbox":"admin.example","txt":"example"}}"#,
+            serde_json::to_string(&rp).unwrap()
+        );
+        match resolved(17, b"\x05admin\x07example\x00") {
+            RRecordTypes::Malformed(_) => (),
+            record => panic!("{:?}", record),
+        }
     }
 
+    #[test]
+    fn test_dname_record() {
+        let dname = resolved(39, b"\x03new\x07example\x00");
+        assert_eq!(DnsType::DNAME, dname.rrtype());
+        assert_eq!("new.example", dname.to_string());
+        assert_eq!(
+            r#"{"DNAME":{"target":"new.example"}}"#,
+            serde_json::to_string(&dname).unwrap()
+        );
+        // A target compressed against the owner name, the root.
+        let dname = resolved(39, b"\x03new\xc0\x00");
+        assert_eq!("new", dname.to_string());
+        match resolved(39, b"\x03new") {
+            RRecordTypes::Malformed(_) => (),
+            record => panic!("{:?}", record),
+        }
+    }
 }
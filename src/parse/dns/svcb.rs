@@ -0,0 +1,341 @@
+use crate::parse::dns::*;
+use data_encoding::BASE64;
+use nom::*;
+use serde::{Serialize, Serializer};
+use serde_derive::Serialize;
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// A SvcParamKey (RFC 9460 14.3.2), shown by name or as `keyNNNNN`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SvcParamKey(pub u16);
+
+impl std::fmt::Display for SvcParamKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0 {
+            0 => write!(f, "mandatory"),
+            1 => write!(f, "alpn"),
+            2 => write!(f, "no-default-alpn"),
+            3 => write!(f, "port"),
+            4 => write!(f, "ipv4hint"),
+            5 => write!(f, "ech"),
+            6 => write!(f, "ipv6hint"),
+            7 => write!(f, "dohpath"),
+            key => write!(f, "key{}", key),
+        }
+    }
+}
+
+impl Serialize for SvcParamKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn serialize_alpn<S: Serializer>(ids: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(ids.iter().map(|id| String::from_utf8_lossy(id)))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SvcParam {
+    Mandatory(Vec<SvcParamKey>),
+    #[serde(serialize_with = "serialize_alpn")]
+    Alpn(Vec<Vec<u8>>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4hint(Vec<Ipv4Addr>),
+    #[serde(serialize_with = "serialize_base64")]
+    Ech(Vec<u8>),
+    Ipv6hint(Vec<Ipv6Addr>),
+    Dohpath(String),
+    Unknown {
+        key: SvcParamKey,
+        #[serde(serialize_with = "serialize_hex")]
+        value: Vec<u8>,
+    },
+}
+
+impl SvcParam {
+    /// Decode the value of `key`, `None` if it does not fit the key.
+    fn new(key: u16, value: &[u8]) -> Option<SvcParam> {
+        let param = match key {
+            0 if !value.is_empty() && value.len() % 2 == 0 => SvcParam::Mandatory(
+                value
+                    .chunks(2)
+                    .map(|k| SvcParamKey(u16::from_be_bytes([k[0], k[1]])))
+                    .collect(),
+            ),
+            1 => {
+                let mut ids = Vec::new();
+                let mut i = value;
+                while let Some((&len, rest)) = i.split_first() {
+                    let id = rest.get(..usize::from(len)).filter(|id| !id.is_empty())?;
+                    ids.push(id.to_vec());
+                    i = &rest[id.len()..];
+                }
+                if ids.is_empty() {
+                    return None;
+                }
+                SvcParam::Alpn(ids)
+            }
+            2 if value.is_empty() => SvcParam::NoDefaultAlpn,
+            3 if value.len() == 2 => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+            4 if !value.is_empty() && value.len() % 4 == 0 => SvcParam::Ipv4hint(
+                value
+                    .chunks(4)
+                    .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                    .collect(),
+            ),
+            5 => SvcParam::Ech(value.to_vec()),
+            6 if !value.is_empty() && value.len() % 16 == 0 => {
+                let mut addrs = Vec::new();
+                for a in value.chunks(16) {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(a);
+                    addrs.push(Ipv6Addr::from(octets));
+                }
+                SvcParam::Ipv6hint(addrs)
+            }
+            7 => SvcParam::Dohpath(String::from_utf8(value.to_vec()).ok()?),
+            0..=7 => return None,
+            _ => SvcParam::Unknown {
+                key: SvcParamKey(key),
+                value: value.to_vec(),
+            },
+        };
+        Some(param)
+    }
+
+    pub fn key(&self) -> SvcParamKey {
+        match self {
+            SvcParam::Mandatory(_) => SvcParamKey(0),
+            SvcParam::Alpn(_) => SvcParamKey(1),
+            SvcParam::NoDefaultAlpn => SvcParamKey(2),
+            SvcParam::Port(_) => SvcParamKey(3),
+            SvcParam::Ipv4hint(_) => SvcParamKey(4),
+            SvcParam::Ech(_) => SvcParamKey(5),
+            SvcParam::Ipv6hint(_) => SvcParamKey(6),
+            SvcParam::Dohpath(_) => SvcParamKey(7),
+            SvcParam::Unknown { key, .. } => *key,
+        }
+    }
+
+    /// The value in wire format.
+    pub(crate) fn to_wire(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys.iter() {
+                    out.extend_from_slice(&key.0.to_be_bytes());
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids.iter() {
+                    out.push(id.len() as u8);
+                    out.extend_from_slice(id);
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => out.extend_from_slice(&port.to_be_bytes()),
+            SvcParam::Ipv4hint(addrs) => {
+                for addr in addrs.iter() {
+                    out.extend_from_slice(&addr.octets());
+                }
+            }
+            SvcParam::Ech(config) => out.extend_from_slice(config),
+            SvcParam::Ipv6hint(addrs) => {
+                for addr in addrs.iter() {
+                    out.extend_from_slice(&addr.octets());
+                }
+            }
+            SvcParam::Dohpath(path) => out.extend_from_slice(path.as_bytes()),
+            SvcParam::Unknown { value, .. } => out.extend_from_slice(value),
+        }
+        out
+    }
+}
+
+/// Append `value` as a presentation format value, escaping what a
+/// zone file parser would otherwise split on. In value lists a `,` within
+/// an item is escaped twice (RFC 9460 appendix A.1).
+fn escape_value(value: &[u8], in_list: bool, out: &mut String) {
+    for &b in value {
+        match b {
+            b',' if in_list => out.push_str("\\\\,"),
+            b'\\' if in_list => out.push_str("\\\\\\\\"),
+            b'\\' | b'"' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x21..=0x7e if b != b';' => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\{:03}", b);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |items: Vec<String>| items.join(",");
+        match self {
+            SvcParam::Mandatory(keys) => write!(
+                f,
+                "mandatory={}",
+                join(keys.iter().map(|k| k.to_string()).collect())
+            ),
+            SvcParam::Alpn(ids) => {
+                let mut value = String::new();
+                for (i, id) in ids.iter().enumerate() {
+                    if i > 0 {
+                        value.push(',');
+                    }
+                    escape_value(id, true, &mut value);
+                }
+                write!(f, "alpn={}", value)
+            }
+            SvcParam::NoDefaultAlpn => write!(f, "no-default-alpn"),
+            SvcParam::Port(port) => write!(f, "port={}", port),
+            SvcParam::Ipv4hint(addrs) => write!(
+                f,
+                "ipv4hint={}",
+                join(addrs.iter().map(|a| a.to_string()).collect())
+            ),
+            SvcParam::Ech(config) => write!(f, "ech={}", BASE64.encode(config)),
+            SvcParam::Ipv6hint(addrs) => write!(
+                f,
+                "ipv6hint={}",
+                join(addrs.iter().map(|a| a.to_string()).collect())
+            ),
+            SvcParam::Dohpath(path) => {
+                let mut value = String::new();
+                escape_value(path.as_bytes(), false, &mut value);
+                write!(f, "dohpath={}", value)
+            }
+            SvcParam::Unknown { key, value } if value.is_empty() => write!(f, "{}", key),
+            SvcParam::Unknown { key, value } => {
+                let mut escaped = String::new();
+                escape_value(value, false, &mut escaped);
+                write!(f, "{}={}", key, escaped)
+            }
+        }
+    }
+}
+
+/// SVCB and HTTPS rdata (RFC 9460).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SvcbRecord {
+    pub priority: u16,
+    pub target: DomainName,
+    pub params: Vec<SvcParam>,
+}
+
+impl SvcbRecord {
+    named!(parse_param<&[u8], (u16, &[u8])>, do_parse!(
+        key: be_u16 >>
+        value: length_bytes!(be_u16) >>
+        ((key, value))
+    ));
+
+    pub fn new(rr: &ResourceRecord, full_dns_message: &[u8]) -> Result<SvcbRecord, DnsParseError> {
+        let invalid = || rr.invalid_rdata(full_dns_message);
+        let (i, priority) = be_u16(&rr.rdata[..]).map_err(|_| invalid())?;
+        let (mut i, target_chain) = parse_compressed_chain(i).map_err(|_| invalid())?;
+        let target = target_chain.resolve_name(full_dns_message)?;
+
+        let mut params: Vec<SvcParam> = Vec::new();
+        while !i.is_empty() {
+            let (rest, (key, value)) = Self::parse_param(i).map_err(|_| invalid())?;
+            // Keys must be strictly increasing (RFC 9460 2.2).
+            if params.last().is_some_and(|p| p.key().0 >= key) {
+                return Err(invalid());
+            }
+            params.push(SvcParam::new(key, value).ok_or_else(invalid)?);
+            i = rest;
+        }
+        Ok(SvcbRecord {
+            priority,
+            target,
+            params,
+        })
+    }
+}
+
+impl std::fmt::Display for SvcbRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        for param in self.params.iter() {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::dns::resource_record::tests::resolved;
+
+    // Test vectors from RFC 9460 appendix D.
+    #[test]
+    fn test_alias_mode() {
+        let record = resolved(65, b"\x00\x00\x03foo\x07example\x03com\x00");
+        assert!(matches!(record, RRecordTypes::HTTPS(_)));
+        assert_eq!("0 foo.example.com", record.to_string());
+    }
+
+    #[test]
+    fn test_service_mode() {
+        let record = resolved(64, b"\x00\x01\x00\x00\x03\x00\x02\x00\x35");
+        assert_eq!("1 . port=53", record.to_string());
+
+        let record = resolved(
+            64,
+            b"\x00\x01\x03foo\x07example\x03com\x00\x02\x9b\x00\x05hello",
+        );
+        assert_eq!("1 foo.example.com key667=hello", record.to_string());
+
+        let mut rdata = b"\x00\x01\x03foo\x07example\x03com\x00\x00\x06\x00\x20".to_vec();
+        rdata.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        rdata.extend_from_slice(&"2001:db8::53:1".parse::<Ipv6Addr>().unwrap().octets());
+        let record = resolved(64, &rdata);
+        assert_eq!(
+            "1 foo.example.com ipv6hint=2001:db8::1,2001:db8::53:1",
+            record.to_string()
+        );
+    }
+
+    #[test]
+    fn test_mandatory_and_alpn() {
+        let rdata = b"\x00\x10\x03foo\x07example\x03org\x00\
+                      \x00\x00\x00\x04\x00\x01\x00\x04\
+                      \x00\x01\x00\x09\x02h2\x05h3-19\
+                      \x00\x04\x00\x04\xc0\x00\x02\x01";
+        let record = resolved(64, rdata);
+        assert_eq!(
+            "16 foo.example.org mandatory=alpn,ipv4hint alpn=h2,h3-19 ipv4hint=192.0.2.1",
+            record.to_string()
+        );
+        assert_eq!(
+            r#"{"SVCB":{"priority":16,"target":"foo.example.org","params":[{"mandatory":["alpn","ipv4hint"]},{"alpn":["h2","h3-19"]},{"ipv4hint":["192.0.2.1"]}]}}"#,
+            serde_json::to_string(&record).unwrap()
+        );
+
+        let record = resolved(64, b"\x00\x01\x00\x00\x01\x00\x0c\x08f\\oo,bar\x02h2");
+        assert_eq!(r#"1 . alpn=f\\\\oo\\,bar,h2"#, record.to_string());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        // Keys out of order.
+        let record = resolved(
+            64,
+            b"\x00\x01\x00\x00\x03\x00\x02\x00\x35\x00\x01\x00\x03\x02h2",
+        );
+        assert!(matches!(record, RRecordTypes::Malformed(_)));
+        // Port with a bad length.
+        let record = resolved(64, b"\x00\x01\x00\x00\x03\x00\x01\x35");
+        assert!(matches!(record, RRecordTypes::Malformed(_)));
+    }
+}
@@ -0,0 +1,204 @@
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+/// An SPF mechanism (RFC 7208 5), e.g. `-all` or `ip4:192.0.2.0/24`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SpfMechanism {
+    pub qualifier: char,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// An SPF modifier (RFC 7208 6), e.g. `redirect=_spf.example.com`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SpfModifier {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SpfRecord {
+    pub mechanisms: Vec<SpfMechanism>,
+    pub modifiers: Vec<SpfModifier>,
+}
+
+impl SpfRecord {
+    /// Parse a `v=spf1` policy, `None` for any other text.
+    pub fn parse(text: &str) -> Option<SpfRecord> {
+        let mut terms = text.split_ascii_whitespace();
+        if !terms.next()?.eq_ignore_ascii_case("v=spf1") {
+            return None;
+        }
+        let mut mechanisms = Vec::new();
+        let mut modifiers = Vec::new();
+        for term in terms {
+            let name_end = term
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+                .unwrap_or(term.len());
+            if name_end > 0 && term[name_end..].starts_with('=') {
+                modifiers.push(SpfModifier {
+                    name: term[..name_end].to_string(),
+                    value: term[name_end + 1..].to_string(),
+                });
+                continue;
+            }
+            let (qualifier, term) = match term.chars().next() {
+                Some(c) if "+-~?".contains(c) => (c, &term[1..]),
+                _ => ('+', term),
+            };
+            let (name, value) = match term.find([':', '/']) {
+                Some(i) if term[i..].starts_with(':') => (&term[..i], Some(&term[i + 1..])),
+                Some(i) => (&term[..i], Some(&term[i..])),
+                None => (term, None),
+            };
+            mechanisms.push(SpfMechanism {
+                qualifier,
+                name: name.to_string(),
+                value: value.map(String::from),
+            });
+        }
+        Some(SpfRecord {
+            mechanisms,
+            modifiers,
+        })
+    }
+}
+
+/// A `tag=value;` list, as used by DKIM (RFC 6376 3.2) and DMARC.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct TagList(pub BTreeMap<String, String>);
+
+impl TagList {
+    pub fn parse(text: &str) -> Option<TagList> {
+        let mut tags = BTreeMap::new();
+        for spec in text.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, value) = spec.split_once('=')?;
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return None;
+            }
+            tags.insert(name.to_string(), value.trim().to_string());
+        }
+        Some(TagList(tags))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// A domain ownership token, e.g. `google-site-verification=...`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SiteVerification {
+    pub provider: String,
+    pub token: String,
+}
+
+impl SiteVerification {
+    pub fn parse(text: &str) -> Option<SiteVerification> {
+        let (provider, token) = text.split_once(['=', ':'])?;
+        let token = token.trim();
+        let known = provider == "MS" || provider.to_ascii_lowercase().ends_with("verification");
+        if !known || token.is_empty() || provider.contains(char::is_whitespace) {
+            return None;
+        }
+        Some(SiteVerification {
+            provider: provider.to_string(),
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Well-known payloads carried in TXT records.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum TxtPayload {
+    Spf(SpfRecord),
+    Dmarc(TagList),
+    Dkim(TagList),
+    Verification(SiteVerification),
+}
+
+impl TxtPayload {
+    /// Classify the concatenated text of a TXT record.
+    pub fn classify(text: &str) -> Option<TxtPayload> {
+        if let Some(spf) = SpfRecord::parse(text) {
+            return Some(TxtPayload::Spf(spf));
+        }
+        if let Some(mut tags) = TagList::parse(text) {
+            let version = tags.get("v").map(str::to_ascii_uppercase);
+            if version.as_deref() == Some("DMARC1") {
+                return Some(TxtPayload::Dmarc(tags));
+            }
+            let dkim = version.as_deref() == Some("DKIM1")
+                || (version.is_none() && tags.get("k").is_some());
+            if dkim && tags.get("p").is_some() {
+                // The base64 key may be folded with whitespace.
+                if let Some(key) = tags.0.get_mut("p") {
+                    key.retain(|c| !c.is_ascii_whitespace());
+                }
+                return Some(TxtPayload::Dkim(tags));
+            }
+        }
+        SiteVerification::parse(text).map(TxtPayload::Verification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_spf() {
+        let text = "v=spf1 ip4:192.0.2.0/24 a/24 include:_spf.example.com ~all exp=explain";
+        let spf = match TxtPayload::classify(text) {
+            Some(TxtPayload::Spf(spf)) => spf,
+            payload => panic!("{:?}", payload),
+        };
+        let mechanisms: Vec<(char, &str, Option<&str>)> = spf
+            .mechanisms
+            .iter()
+            .map(|m| (m.qualifier, m.name.as_str(), m.value.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                ('+', "ip4", Some("192.0.2.0/24")),
+                ('+', "a", Some("/24")),
+                ('+', "include", Some("_spf.example.com")),
+                ('~', "all", None),
+            ],
+            mechanisms
+        );
+        assert_eq!("exp", spf.modifiers[0].name);
+        assert_eq!(None, TxtPayload::classify("v=spf10 -all"));
+    }
+
+    #[test]
+    fn test_classify_tag_lists() {
+        let dmarc = TxtPayload::classify("v=DMARC1; p=reject; rua=mailto:d@example.com").unwrap();
+        assert_eq!(
+            r#"{"Dmarc":{"p":"reject","rua":"mailto:d@example.com","v":"DMARC1"}}"#,
+            serde_json::to_string(&dmarc).unwrap()
+        );
+        match TxtPayload::classify("k=rsa; p=MIGf MA0G;") {
+            Some(TxtPayload::Dkim(tags)) => assert_eq!(Some("MIGfMA0G"), tags.get("p")),
+            payload => panic!("{:?}", payload),
+        }
+        assert_eq!(None, TxtPayload::classify("p=no version; x=y"));
+    }
+
+    #[test]
+    fn test_classify_verification() {
+        let payload = TxtPayload::classify("google-site-verification=abc123").unwrap();
+        assert_eq!(
+            TxtPayload::Verification(SiteVerification {
+                provider: String::from("google-site-verification"),
+                token: String::from("abc123"),
+            }),
+            payload
+        );
+        assert!(TxtPayload::classify("MS=ms12345").is_some());
+        assert_eq!(None, TxtPayload::classify("hello world"));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::parse::dns::*;
+use serde::{Serialize, Serializer};
+use serde_derive::Serialize;
+
+/// Prerequisite checks of an UPDATE message (RFC 2136 2.4).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prerequisite {
+    NameInUse,
+    NameNotInUse,
+    RrsetExists,
+    RrsetExistsValue,
+    RrsetNotExists,
+}
+
+impl std::fmt::Display for Prerequisite {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Prerequisite::NameInUse => "name-in-use",
+            Prerequisite::NameNotInUse => "name-not-in-use",
+            Prerequisite::RrsetExists => "rrset-exists",
+            Prerequisite::RrsetExistsValue => "rrset-exists-value",
+            Prerequisite::RrsetNotExists => "rrset-not-exists",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What a prerequisite or update record asks for (RFC 2136 2.4, 2.5).
+/// Records that fit none of the forms are `Invalid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateOperation {
+    Prereq(Prerequisite),
+    Add,
+    DeleteRrset,
+    DeleteName,
+    DeleteRr,
+    Invalid,
+}
+
+impl std::fmt::Display for UpdateOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UpdateOperation::Prereq(prereq) => write!(f, "prereq:{}", prereq),
+            UpdateOperation::Add => write!(f, "add"),
+            UpdateOperation::DeleteRrset => write!(f, "delete-rrset"),
+            UpdateOperation::DeleteName => write!(f, "delete-name"),
+            UpdateOperation::DeleteRr => write!(f, "delete-rr"),
+            UpdateOperation::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+impl Serialize for UpdateOperation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// One prerequisite or update record of an UPDATE message.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UpdateEntry {
+    pub operation: UpdateOperation,
+    pub name: DomainName,
+    pub rrtype: DnsType,
+    pub ttl: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rdata: Option<RRecordTypes>,
+}
+
+impl UpdateEntry {
+    fn new(operation: UpdateOperation, rr: &ResourceRecord) -> UpdateEntry {
+        let rdata = match operation {
+            UpdateOperation::Prereq(Prerequisite::RrsetExistsValue)
+            | UpdateOperation::Add
+            | UpdateOperation::DeleteRr
+            | UpdateOperation::Invalid => rr.record.clone(),
+            _ => None,
+        };
+        UpdateEntry {
+            operation,
+            name: rr.name.clone(),
+            rrtype: rr.rrtype.clone(),
+            ttl: rr.ttl,
+            rdata,
+        }
+    }
+
+    fn prerequisite(rr: &ResourceRecord, zone_class: &DnsClass) -> UpdateEntry {
+        let empty = rr.rdata.is_empty();
+        let any = rr.rrtype == DnsType::ALL;
+        let prereq = match &rr.rrclass {
+            _ if rr.ttl != 0 => None,
+            DnsClass::ALL if empty && any => Some(Prerequisite::NameInUse),
+            DnsClass::ALL if empty => Some(Prerequisite::RrsetExists),
+            DnsClass::NONE if empty && any => Some(Prerequisite::NameNotInUse),
+            DnsClass::NONE if empty => Some(Prerequisite::RrsetNotExists),
+            class if class == zone_class && !any => Some(Prerequisite::RrsetExistsValue),
+            _ => None,
+        };
+        let operation = prereq.map_or(UpdateOperation::Invalid, UpdateOperation::Prereq);
+        UpdateEntry::new(operation, rr)
+    }
+
+    fn update(rr: &ResourceRecord, zone_class: &DnsClass) -> UpdateEntry {
+        let empty = rr.rdata.is_empty();
+        let any = rr.rrtype == DnsType::ALL;
+        let operation = match &rr.rrclass {
+            class if class == zone_class && !any => UpdateOperation::Add,
+            DnsClass::ALL if rr.ttl == 0 && empty && any => UpdateOperation::DeleteName,
+            DnsClass::ALL if rr.ttl == 0 && empty => UpdateOperation::DeleteRrset,
+            DnsClass::NONE if rr.ttl == 0 && !any => UpdateOperation::DeleteRr,
+            _ => UpdateOperation::Invalid,
+        };
+        UpdateEntry::new(operation, rr)
+    }
+}
+
+/// `add host.example 3600 A 192.0.2.1`, `delete-rrset host.example AAAA`.
+impl std::fmt::Display for UpdateEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.operation, self.name)?;
+        if self.operation == UpdateOperation::Add {
+            write!(f, " {}", self.ttl)?;
+        }
+        if self.rrtype != DnsType::ALL {
+            write!(f, " {}", self.rrtype)?;
+        }
+        if let Some(rdata) = &self.rdata {
+            write!(f, " {}", rdata)?;
+        }
+        Ok(())
+    }
+}
+
+/// The sections of an UPDATE message by their RFC 2136 meaning.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsUpdate {
+    pub zone: Option<Query>,
+    pub prerequisites: Vec<UpdateEntry>,
+    pub updates: Vec<UpdateEntry>,
+}
+
+impl DnsMessage<'_> {
+    /// Read the message as an UPDATE, `None` for other opcodes.
+    ///
+    /// The zone, prerequisite and update sections are the question,
+    /// answer and authority sections. Rdata is taken from resolved records.
+    pub fn update(&self) -> Option<DnsUpdate> {
+        if self.header.flags.opcode != Opcode::Update {
+            return None;
+        }
+        let zone = self.queries.first().cloned();
+        let zone_class = zone.as_ref().map_or(DnsClass::IN, |z| z.qclass.clone());
+        Some(DnsUpdate {
+            zone,
+            prerequisites: self
+                .answers
+                .iter()
+                .map(|rr| UpdateEntry::prerequisite(rr, &zone_class))
+                .collect(),
+            updates: self
+                .nsrecords
+                .iter()
+                .map(|rr| UpdateEntry::update(rr, &zone_class))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        name: &str,
+        rrclass: DnsClass,
+        ttl: u32,
+        record: RRecordTypes,
+    ) -> ResourceRecord<'static> {
+        ResourceRecord::new(name.parse().unwrap(), rrclass, ttl, record)
+    }
+
+    fn empty(rrtype: u16) -> RRecordTypes {
+        RRecordTypes::Raw(RawRecord {
+            rrtype,
+            rdlength: 0,
+            rdata: vec![],
+        })
+    }
+
+    fn a(address: &str) -> RRecordTypes {
+        RRecordTypes::A(ARecord {
+            address: address.parse().unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_update_operations() {
+        let flags = Flags {
+            opcode: Opcode::Update,
+            ..Default::default()
+        };
+        let msg = DnsMessageBuilder::new(7)
+            .flags(flags)
+            .query("example.com".parse().unwrap(), DnsType::SOA, DnsClass::IN)
+            .answer(record("host.example.com", DnsClass::NONE, 0, empty(255)))
+            .answer(record("example.com", DnsClass::ALL, 0, empty(6)))
+            .answer(record("ns.example.com", DnsClass::IN, 0, a("192.0.2.53")))
+            .authority(record(
+                "host.example.com",
+                DnsClass::IN,
+                3600,
+                a("192.0.2.1"),
+            ))
+            .authority(record("old.example.com", DnsClass::ALL, 0, empty(255)))
+            .authority(record("www.example.com", DnsClass::ALL, 0, empty(28)))
+            .authority(record("dc.example.com", DnsClass::NONE, 0, a("192.0.2.2")))
+            .authority(record("bad.example.com", DnsClass::NONE, 60, empty(255)))
+            .build();
+        let wire = msg.encode(true);
+        let (_rest, parsed) = dns_message(&wire, true).unwrap();
+        let update = parsed.update().unwrap();
+        assert_eq!(
+            "example.com",
+            update.zone.as_ref().map(|z| z.name.to_string()).unwrap()
+        );
+        let lines = |entries: &[UpdateEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.to_string()).collect()
+        };
+        assert_eq!(
+            vec![
+                "prereq:name-not-in-use host.example.com",
+                "prereq:rrset-exists example.com SOA",
+                "prereq:rrset-exists-value ns.example.com A 192.0.2.53",
+            ],
+            lines(&update.prerequisites)
+        );
+        assert_eq!(
+            vec![
+                "add host.example.com 3600 A 192.0.2.1",
+                "delete-name old.example.com",
+                "delete-rrset www.example.com AAAA",
+                "delete-rr dc.example.com A 192.0.2.2",
+                "invalid bad.example.com \\# 0",
+            ],
+            lines(&update.updates)
+        );
+        assert_eq!(
+            r#"{"operation":"delete-rr","name":"dc.example.com","rrtype":"A","ttl":0,"rdata":{"A":{"address":"192.0.2.2"}}}"#,
+            serde_json::to_string(&update.updates[3]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_query_is_not_update() {
+        let msg = DnsMessageBuilder::new(1)
+            .query("example.com".parse().unwrap(), DnsType::A, DnsClass::IN)
+            .build();
+        assert_eq!(None, msg.update());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::parse::dns::*;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+
+/// A way a message breaks the DNS specifications, found by
+/// `DnsMessage::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A header count that differs from the records in the section.
+    CountMismatch {
+        section: Section,
+        count: u16,
+        records: usize,
+    },
+    /// Bytes after the last record.
+    TrailingBytes(usize),
+    /// More than one OPT record (RFC 6891 6.1.1).
+    MultipleOpt(usize),
+    /// An OPT record outside the additional section.
+    OptOutsideAdditional(Section),
+    /// An OPT record owned by another name than the root.
+    OptNotRoot(DomainName),
+    /// A TTL with the most significant bit set (RFC 2181 8).
+    TtlHighBit {
+        section: Section,
+        index: usize,
+        ttl: u32,
+    },
+    /// A standard query (QR=0) with records in the answer section.
+    AnswersInQuery(usize),
+    /// A name longer than 255 octets in wire format.
+    NameTooLong { section: Section, index: usize },
+    /// A response with another id than its query.
+    IdMismatch { query: u16, response: u16 },
+    /// A response whose question section differs from its query.
+    QuestionMismatch,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Violation::CountMismatch {
+                section,
+                count,
+                records,
+            } => write!(f, "{} count {} with {} records", section, count, records),
+            Violation::TrailingBytes(len) => write!(f, "{} trailing bytes", len),
+            Violation::MultipleOpt(count) => write!(f, "{} OPT records", count),
+            Violation::OptOutsideAdditional(section) => write!(f, "OPT record in {}", section),
+            Violation::OptNotRoot(name) => write!(f, "OPT record owned by {}", name),
+            Violation::TtlHighBit {
+                section,
+                index,
+                ttl,
+            } => write!(f, "TTL {} with high bit set ({} #{})", ttl, section, index),
+            Violation::AnswersInQuery(count) => write!(f, "query with {} answers", count),
+            Violation::NameTooLong { section, index } => write!(
+                f,
+                "name longer than {} octets ({} #{})",
+                NAME_MAX_LEN, section, index
+            ),
+            Violation::IdMismatch { query, response } => {
+                write!(f, "response id {} to query id {}", response, query)
+            }
+            Violation::QuestionMismatch => write!(f, "question differs from query"),
+        }
+    }
+}
+
+impl Serialize for Violation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn is_name_too_long(rr: &ResourceRecord) -> bool {
+    let rdata_name_too_long = matches!(
+        &rr.record,
+        Some(RRecordTypes::Malformed(MalformedRecord {
+            error: DnsParseError::InvalidName {
+                reason: NameError::NameTooLong,
+                ..
+            },
+            ..
+        }))
+    );
+    rr.name.wire_len() > NAME_MAX_LEN || rdata_name_too_long
+}
+
+impl DnsMessage<'_> {
+    fn record_sections(&self) -> [(Section, &Vec<ResourceRecord<'_>>); 3] {
+        [
+            (Section::Answer, &self.answers),
+            (Section::Authority, &self.nsrecords),
+            (Section::Additional, &self.arecords),
+        ]
+    }
+
+    /// Check the message against the DNS specifications. `rest` is what
+    /// `dns_message()` left after the message.
+    ///
+    /// Names in rdata are only checked in resolved records.
+    pub fn validate(&self, rest: &[u8]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let counts = [
+            (Section::Question, self.header.qdcount, self.queries.len()),
+            (Section::Answer, self.header.ancount, self.answers.len()),
+            (
+                Section::Authority,
+                self.header.nscount,
+                self.nsrecords.len(),
+            ),
+            (
+                Section::Additional,
+                self.header.arcount,
+                self.arecords.len(),
+            ),
+        ];
+        for (section, count, records) in counts {
+            if usize::from(count) != records {
+                violations.push(Violation::CountMismatch {
+                    section,
+                    count,
+                    records,
+                });
+            }
+        }
+        if !rest.is_empty() {
+            violations.push(Violation::TrailingBytes(rest.len()));
+        }
+
+        let mut opt_count = 0;
+        for (section, records) in self.record_sections() {
+            for (index, rr) in records.iter().enumerate() {
+                if rr.rrtype == DnsType::OPT {
+                    opt_count += 1;
+                    if section != Section::Additional {
+                        violations.push(Violation::OptOutsideAdditional(section));
+                    }
+                    if !rr.name.is_root() {
+                        violations.push(Violation::OptNotRoot(rr.name.clone()));
+                    }
+                } else if rr.ttl & 0x8000_0000 != 0 {
+                    violations.push(Violation::TtlHighBit {
+                        section,
+                        index,
+                        ttl: rr.ttl,
+                    });
+                }
+                if is_name_too_long(rr) {
+                    violations.push(Violation::NameTooLong { section, index });
+                }
+            }
+        }
+        if opt_count > 1 {
+            violations.push(Violation::MultipleOpt(opt_count));
+        }
+
+        for (index, query) in self.queries.iter().enumerate() {
+            if query.name.wire_len() > NAME_MAX_LEN {
+                violations.push(Violation::NameTooLong {
+                    section: Section::Question,
+                    index,
+                });
+            }
+        }
+        let flags = &self.header.flags;
+        if flags.qr == Qr::Query && flags.opcode == Opcode::Query && !self.answers.is_empty() {
+            violations.push(Violation::AnswersInQuery(self.answers.len()));
+        }
+        violations
+    }
+
+    /// Check that this response answers `query`: same id and the same
+    /// questions, names compared without regard to case.
+    pub fn validate_response(&self, query: &DnsMessage) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if self.header.id != query.header.id {
+            violations.push(Violation::IdMismatch {
+                query: query.header.id,
+                response: self.header.id,
+            });
+        }
+        if self.queries != query.queries {
+            violations.push(Violation::QuestionMismatch);
+        }
+        violations
+    }
+}
+
+type QueryKey = (SocketAddr, SocketAddr, u16);
+
+/// Queries waiting for their response, so that responses can be checked
+/// with `DnsMessage::validate_response`. At most `capacity` queries are
+/// kept, the oldest are dropped first.
+#[derive(Clone, Debug)]
+pub struct QueryTracker {
+    capacity: usize,
+    queries: BTreeMap<QueryKey, OwnedDnsMessage>,
+    order: VecDeque<QueryKey>,
+}
+
+impl QueryTracker {
+    pub fn new(capacity: usize) -> QueryTracker {
+        QueryTracker {
+            capacity,
+            queries: BTreeMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Remember the header and questions of a query from `client` to
+    /// `server`.
+    pub fn add_query(&mut self, client: SocketAddr, server: SocketAddr, query: &DnsMessage) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = (client, server, query.header.id);
+        let question = DnsMessage {
+            header: query.header.clone(),
+            queries: query.queries.clone(),
+            answers: Vec::new(),
+            nsrecords: Vec::new(),
+            arecords: Vec::new(),
+        };
+        if self.queries.insert(key, question).is_none() {
+            self.order.push_back(key);
+            if self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.queries.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    /// Check a response from `server` to `client` against the query with
+    /// the same id. When there is none but a single other query is
+    /// outstanding between the two, the response is checked against that
+    /// one, which reports the id mismatch. Responses to queries that were
+    /// not seen give no violations.
+    pub fn check_response(
+        &mut self,
+        server: SocketAddr,
+        client: SocketAddr,
+        response: &DnsMessage,
+    ) -> Vec<Violation> {
+        let mut key = (client, server, response.header.id);
+        if !self.queries.contains_key(&key) {
+            let mut flow = self
+                .queries
+                .range((client, server, 0)..=(client, server, u16::MAX));
+            key = match (flow.next(), flow.next()) {
+                (Some((&only, _)), None) => only,
+                _ => return Vec::new(),
+            };
+        }
+        match self.queries.remove(&key) {
+            Some(query) => {
+                // Most responses are for recent queries.
+                if let Some(i) = self.order.iter().rposition(|k| *k == key) {
+                    self.order.remove(i);
+                }
+                response.validate_response(&query)
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DNS_RESPONSE: &[u8] = include_bytes!("../../../fixtures/dns/dns_response1.bin");
+
+    fn opt(name: &str) -> ResourceRecord<'static> {
+        let opt = OptRecord {
+            udp_payload_size: 1232,
+            e_rcode: 0,
+            version: 0,
+            opt_do: 0,
+            z: 0,
+            options: vec![],
+        };
+        ResourceRecord::new(
+            name.parse().unwrap(),
+            DnsClass::from(1232),
+            0,
+            RRecordTypes::OPT(opt),
+        )
+    }
+
+    #[test]
+    fn test_valid_message() {
+        let (rest, msg) = dns_message(DNS_RESPONSE, true).unwrap();
+        assert_eq!(Vec::<Violation>::new(), msg.validate(rest));
+        assert_eq!(Vec::<Violation>::new(), msg.validate_response(&msg));
+    }
+
+    #[test]
+    fn test_violations() {
+        let a = ResourceRecord::new(
+            "example.com".parse().unwrap(),
+            DnsClass::IN,
+            0x8000_0000,
+            RRecordTypes::A(ARecord {
+                address: "192.0.2.1".parse().unwrap(),
+            }),
+        );
+        let mut msg = DnsMessageBuilder::new(1)
+            .query("example.com".parse().unwrap(), DnsType::A, DnsClass::IN)
+            .answer(a)
+            .authority(opt("."))
+            .additional(opt("example.com"))
+            .build();
+        msg.header.qdcount = 2;
+        let violations: Vec<String> = msg
+            .validate(&[0, 0])
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "question count 2 with 1 records",
+                "2 trailing bytes",
+                "TTL 2147483648 with high bit set (answer #0)",
+                "OPT record in authority",
+                "OPT record owned by example.com",
+                "2 OPT records",
+                "query with 1 answers",
+            ],
+            violations
+        );
+        assert_eq!(
+            r#"["2 trailing bytes"]"#,
+            serde_json::to_string(&[Violation::TrailingBytes(2)]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rdata_name_too_long() {
+        let mut label = vec![63];
+        label.extend_from_slice(&[b'a'; 63]);
+        let mut rdata = label.repeat(4);
+        rdata.push(0);
+        let mut data = vec![0, 0, 5, 0, 1, 0, 0, 0, 60];
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(&rdata);
+        let (_, mut rr) = parse_resource_record(&data, &data).unwrap();
+        rr.resolve(&data);
+        let flags = Flags {
+            qr: Qr::Response,
+            ..Default::default()
+        };
+        let msg = DnsMessageBuilder::new(1)
+            .flags(flags)
+            .answer(rr.into_owned())
+            .build();
+        assert_eq!(
+            vec![Violation::NameTooLong {
+                section: Section::Answer,
+                index: 0
+            }],
+            msg.validate(&[])
+        );
+    }
+
+    #[test]
+    fn test_question_mismatch() {
+        let (_, response) = dns_message(DNS_RESPONSE, true).unwrap();
+        let query = DnsMessageBuilder::new(response.header.id + 1)
+            .query("google.com".parse().unwrap(), DnsType::A, DnsClass::IN)
+            .build();
+        assert_eq!(
+            vec![
+                Violation::IdMismatch {
+                    query: response.header.id + 1,
+                    response: response.header.id,
+                },
+                Violation::QuestionMismatch,
+            ],
+            response.validate_response(&query)
+        );
+    }
+
+    #[test]
+    fn test_query_tracker() {
+        let client: SocketAddr = "192.0.2.1:49152".parse().unwrap();
+        let server: SocketAddr = "192.0.2.53:53".parse().unwrap();
+        let (_, response) = dns_message(DNS_RESPONSE, true).unwrap();
+        let query = |id| {
+            DnsMessageBuilder::new(id)
+                .query("google.com".parse().unwrap(), DnsType::MX, DnsClass::IN)
+                .build()
+        };
+        let mut tracker = QueryTracker::new(2);
+        tracker.add_query(client, server, &query(response.header.id));
+        assert!(tracker.check_response(server, client, &response).is_empty());
+
+        tracker.add_query(client, server, &query(1));
+        assert_eq!(
+            vec![Violation::IdMismatch {
+                query: 1,
+                response: response.header.id,
+            }],
+            tracker.check_response(server, client, &response)
+        );
+        assert!(tracker.check_response(server, client, &response).is_empty());
+
+        // The oldest query is forgotten first.
+        for id in 1..=3 {
+            tracker.add_query(client, server, &query(id));
+        }
+        let mut answer = query(1);
+        answer.header.flags.qr = Qr::Response;
+        assert!(tracker.check_response(server, client, &answer).is_empty());
+        answer.header.id = 2;
+        assert!(tracker.check_response(server, client, &answer).is_empty());
+        answer.header.id = 4;
+        assert_eq!(1, tracker.check_response(server, client, &answer).len());
+    }
+
+    #[test]
+    fn test_query_tracker_reused_id() {
+        let client: SocketAddr = "192.0.2.1:49152".parse().unwrap();
+        let server: SocketAddr = "192.0.2.53:53".parse().unwrap();
+        let query = |id| {
+            DnsMessageBuilder::new(id)
+                .query("example.com".parse().unwrap(), DnsType::A, DnsClass::IN)
+                .build()
+        };
+        let answer = |id| {
+            let mut answer = query(id);
+            answer.header.flags.qr = Qr::Response;
+            answer
+        };
+        let mut tracker = QueryTracker::new(2);
+        tracker.add_query(client, server, &query(1));
+        assert!(tracker
+            .check_response(server, client, &answer(1))
+            .is_empty());
+
+        // An answered query does not take up room in the tracker.
+        tracker.add_query(client, server, &query(1));
+        tracker.add_query(client, server, &query(2));
+        assert!(tracker
+            .check_response(server, client, &answer(1))
+            .is_empty());
+        assert!(tracker
+            .check_response(server, client, &answer(2))
+            .is_empty());
+    }
+}
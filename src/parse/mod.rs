@@ -1,2 +1,5 @@
 pub mod dns;
 pub use crate::parse::dns::*;
+
+pub mod packet;
+pub use crate::parse::packet::*;
@@ -0,0 +1,702 @@
+use pcap::Linktype;
+use pnet::packet::{
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::Ipv4Packet,
+    ipv6::Ipv6Packet,
+    tcp::TcpPacket,
+    udp::UdpPacket,
+    Packet,
+};
+use serde::{Serialize, Serializer};
+use serde_derive::Serialize;
+use std::net::IpAddr;
+
+/// Tunnels nested deeper than this are not unwrapped.
+const MAX_TUNNELS: usize = 8;
+
+/// The transport protocol a dns message was carried over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "UDP"),
+            Transport::Tcp => write!(f, "TCP"),
+        }
+    }
+}
+
+/// The encapsulations traffic mirroring delivers packets in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TunnelKind {
+    Gre,
+    /// ERSPAN type I, II or III over GRE.
+    Erspan(u8),
+    Vxlan,
+    Geneve,
+}
+
+impl std::fmt::Display for TunnelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TunnelKind::Gre => write!(f, "GRE"),
+            TunnelKind::Erspan(1) => write!(f, "ERSPAN-I"),
+            TunnelKind::Erspan(2) => write!(f, "ERSPAN-II"),
+            TunnelKind::Erspan(3) => write!(f, "ERSPAN-III"),
+            TunnelKind::Erspan(version) => write!(f, "ERSPAN-{}", version),
+            TunnelKind::Vxlan => write!(f, "VXLAN"),
+            TunnelKind::Geneve => write!(f, "GENEVE"),
+        }
+    }
+}
+
+impl Serialize for TunnelKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A tunnel a packet was unwrapped from, with the outer addresses and the
+/// identifiers of the mirrored source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Tunnel {
+    pub kind: TunnelKind,
+    pub src: IpAddr,
+    pub dest: IpAddr,
+    /// The VXLAN or GENEVE network identifier.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vni: Option<u32>,
+    /// The ERSPAN session ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<u16>,
+    /// The GRE key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<u32>,
+}
+
+impl Tunnel {
+    fn new(kind: TunnelKind, src: IpAddr, dest: IpAddr) -> Tunnel {
+        Tunnel {
+            kind,
+            src,
+            dest,
+            vni: None,
+            session_id: None,
+            key: None,
+        }
+    }
+}
+
+/// `VXLAN 192.0.2.1->192.0.2.2 vni=42`
+impl std::fmt::Display for Tunnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}->{}", self.kind, self.src, self.dest)?;
+        if let Some(vni) = self.vni {
+            write!(f, " vni={}", vni)?;
+        }
+        if let Some(session_id) = self.session_id {
+            write!(f, " session={}", session_id)?;
+        }
+        if let Some(key) = self.key {
+            write!(f, " key={}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// The transport payload of a captured packet and where it was sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsPayload<'a> {
+    pub transport: Transport,
+    pub src: IpAddr,
+    pub sport: u16,
+    pub dest: IpAddr,
+    pub dport: u16,
+    /// 802.1Q and 802.1ad VLAN IDs, outermost first.
+    pub vlans: Vec<u16>,
+    /// The capturing interface, from Linux cooked (SLL2) headers.
+    pub interface: Option<u32>,
+    /// The tunnels the packet was unwrapped from, outermost first.
+    pub tunnels: Vec<Tunnel>,
+    pub payload: &'a [u8],
+}
+
+impl<'a> DnsPayload<'a> {
+    fn tunneled(mut self, tunnel: Tunnel) -> DnsPayload<'a> {
+        self.tunnels.insert(0, tunnel);
+        self
+    }
+}
+
+/// The payload of a packet captured with link type `linktype`, `None` for
+/// unsupported link types and packets without an IPv4 or IPv6 packet
+/// carrying UDP or TCP.
+pub fn link_payload(linktype: Linktype, data: &[u8]) -> Option<DnsPayload<'_>> {
+    match linktype {
+        Linktype::ETHERNET => ethernet_payload(data, 0),
+        Linktype::LINUX_SLL => {
+            let header = data.get(..16)?;
+            let ethertype = EtherType(u16::from_be_bytes([header[14], header[15]]));
+            ethertype_payload(ethertype, &data[16..], 0)
+        }
+        Linktype::LINUX_SLL2 => {
+            let header = data.get(..20)?;
+            let ethertype = EtherType(u16::from_be_bytes([header[0], header[1]]));
+            let interface = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            let payload = ethertype_payload(ethertype, &data[20..], 0)?;
+            Some(DnsPayload {
+                interface: Some(interface),
+                ..payload
+            })
+        }
+        // DLT_RAW is 12 or 14 depending on the platform, LINKTYPE_RAW is 101.
+        Linktype(12) | Linktype(14) | Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => {
+            ip_payload(data)
+        }
+        Linktype::NULL | Linktype::LOOP => {
+            let family = data.get(..4)?;
+            // NULL has the family in the byte order of the capturing host.
+            let family = match u32::from_be_bytes([family[0], family[1], family[2], family[3]]) {
+                family if family > 0xffff => family.swap_bytes(),
+                family => family,
+            };
+            match family {
+                2 => ipv4_payload(&data[4..], 0),
+                // Linux, NetBSD and OpenBSD, FreeBSD, macOS.
+                10 | 24 | 28 | 30 => ipv6_payload(&data[4..], 0),
+                _ => None,
+            }
+        }
+        Linktype::IEEE802_11_RADIOTAP => {
+            let len = usize::from(u16::from_le_bytes([*data.get(2)?, *data.get(3)?]));
+            ieee802_11_payload(data.get(len..)?)
+        }
+        Linktype::IEEE802_11 => ieee802_11_payload(data),
+        _ => None,
+    }
+}
+
+/// The payload of an IP packet of either version.
+pub fn ip_payload(data: &[u8]) -> Option<DnsPayload<'_>> {
+    match data.first()? >> 4 {
+        4 => ipv4_payload(data, 0),
+        6 => ipv6_payload(data, 0),
+        _ => None,
+    }
+}
+
+/// The payload of an unencrypted 802.11 data frame with an LLC/SNAP
+/// header.
+fn ieee802_11_payload(data: &[u8]) -> Option<DnsPayload<'_>> {
+    let (fc0, fc1) = (*data.first()?, *data.get(1)?);
+    let frame_type = (fc0 >> 2) & 3;
+    let subtype = fc0 >> 4;
+    // Data frames without a body have subtype bit 2 set.
+    if frame_type != 2 || subtype & 0x4 != 0 || fc1 & 0x40 != 0 {
+        return None;
+    }
+    let mut header_len = 24;
+    if fc1 & 3 == 3 {
+        header_len += 6;
+    }
+    if subtype & 0x8 != 0 {
+        header_len += 2;
+        if fc1 & 0x80 != 0 {
+            header_len += 4;
+        }
+    }
+    let llc = data.get(header_len..header_len + 8)?;
+    if llc[..6] != [0xaa, 0xaa, 0x03, 0, 0, 0] {
+        return None;
+    }
+    let ethertype = EtherType(u16::from_be_bytes([llc[6], llc[7]]));
+    ethertype_payload(ethertype, &data[header_len + 8..], 0)
+}
+
+/// The payload of an Ethernet frame, `None` for frames without an
+/// IPv4 or IPv6 packet carrying UDP or TCP. `depth` counts the tunnels
+/// the frame was unwrapped from.
+fn ethernet_payload(data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let ethernet = EthernetPacket::new(data)?;
+    let payload = &data[data.len() - ethernet.payload().len()..];
+    ethertype_payload(ethernet.get_ethertype(), payload, depth)
+}
+
+/// Strip VLAN tags, MPLS label stacks and PPPoE session headers down to
+/// the IP packet.
+fn ethertype_payload(
+    mut ethertype: EtherType,
+    mut data: &[u8],
+    depth: usize,
+) -> Option<DnsPayload<'_>> {
+    let mut vlans = Vec::new();
+    let mut payload = loop {
+        match ethertype {
+            EtherTypes::Vlan | EtherTypes::PBridge | EtherTypes::QinQ => {
+                let tag = data.get(..4)?;
+                vlans.push(u16::from_be_bytes([tag[0], tag[1]]) & 0xfff);
+                ethertype = EtherType(u16::from_be_bytes([tag[2], tag[3]]));
+                data = &data[4..];
+            }
+            EtherTypes::Mpls | EtherTypes::MplsMcast => {
+                // Entries up to the one with the bottom of stack bit. The
+                // stack does not tell what follows, the IP version does.
+                loop {
+                    let entry = data.get(..4)?;
+                    data = &data[4..];
+                    if entry[2] & 1 == 1 {
+                        break;
+                    }
+                }
+                ethertype = match data.first()? >> 4 {
+                    4 => EtherTypes::Ipv4,
+                    6 => EtherTypes::Ipv6,
+                    _ => return None,
+                };
+            }
+            EtherTypes::PppoeSession => {
+                let header = data.get(..8)?;
+                ethertype = match u16::from_be_bytes([header[6], header[7]]) {
+                    0x0021 => EtherTypes::Ipv4,
+                    0x0057 => EtherTypes::Ipv6,
+                    _ => return None,
+                };
+                data = &data[8..];
+            }
+            EtherTypes::Ipv4 => break ipv4_payload(data, depth)?,
+            EtherTypes::Ipv6 => break ipv6_payload(data, depth)?,
+            _ => return None,
+        }
+    };
+    // The VLANs of a tunneled packet are those of the inner frame.
+    if payload.tunnels.is_empty() {
+        payload.vlans = vlans;
+    }
+    Some(payload)
+}
+
+/// Fragments other than the first can not be decoded.
+fn ipv4_payload(data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let ipv4 = Ipv4Packet::new(data)?;
+    let header_len = usize::from(ipv4.get_header_length()) * 4;
+    if header_len < 20 || ipv4.get_fragment_offset() != 0 {
+        return None;
+    }
+    let total_len = usize::from(ipv4.get_total_length()).min(data.len());
+    let payload = data.get(header_len..total_len)?;
+    let src = IpAddr::V4(ipv4.get_source());
+    let dest = IpAddr::V4(ipv4.get_destination());
+    transport_payload(ipv4.get_next_level_protocol(), src, dest, payload, depth)
+}
+
+/// Walk the extension headers (RFC 8200 4) to the transport header.
+/// Fragments other than the first can not be decoded.
+fn ipv6_payload(data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let ipv6 = Ipv6Packet::new(data)?;
+    let payload_len = usize::from(ipv6.get_payload_length());
+    let mut payload = data.get(40..)?;
+    payload = &payload[..payload_len.min(payload.len())];
+    let src = IpAddr::V6(ipv6.get_source());
+    let dest = IpAddr::V6(ipv6.get_destination());
+
+    let mut next_header = ipv6.get_next_header();
+    loop {
+        let header_len = match next_header {
+            IpNextHeaderProtocols::Hopopt
+            | IpNextHeaderProtocols::Ipv6Route
+            | IpNextHeaderProtocols::Ipv6Opts => (usize::from(*payload.get(1)?) + 1) * 8,
+            IpNextHeaderProtocols::Ipv6Frag => {
+                let offset = u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]) >> 3;
+                if offset != 0 {
+                    return None;
+                }
+                8
+            }
+            // The authentication header length is in 4-octet units.
+            IpNextHeaderProtocols::Ah => (usize::from(*payload.get(1)?) + 2) * 4,
+            _ => break,
+        };
+        next_header = IpNextHeaderProtocol(*payload.first()?);
+        payload = payload.get(header_len..)?;
+    }
+    transport_payload(next_header, src, dest, payload, depth)
+}
+
+/// The payload of a UDP datagram or TCP segment. Only TCP segments that
+/// hold a whole length-prefixed message (RFC 1035 4.2.2) are decoded,
+/// streams are not reassembled. GRE packets and VXLAN and GENEVE
+/// datagrams are unwrapped to the packet they carry.
+fn transport_payload(
+    protocol: IpNextHeaderProtocol,
+    src: IpAddr,
+    dest: IpAddr,
+    data: &[u8],
+    depth: usize,
+) -> Option<DnsPayload<'_>> {
+    let tunnels = depth < MAX_TUNNELS;
+    match protocol {
+        IpNextHeaderProtocols::Gre if tunnels => gre_payload(src, dest, data, depth + 1),
+        IpNextHeaderProtocols::Udp => {
+            let udp = UdpPacket::new(data)?;
+            let (sport, dport) = (udp.get_source(), udp.get_destination());
+            let payload = &data[8..];
+            // A dns response can be sent to a client port that happens
+            // to be a tunnel port, so fall back to the datagram itself.
+            let inner = match dport {
+                4789 if tunnels => vxlan_payload(src, dest, payload, depth + 1),
+                6081 if tunnels => geneve_payload(src, dest, payload, depth + 1),
+                _ => None,
+            };
+            inner.or(Some(DnsPayload {
+                transport: Transport::Udp,
+                src,
+                sport,
+                dest,
+                dport,
+                vlans: Vec::new(),
+                interface: None,
+                tunnels: Vec::new(),
+                payload,
+            }))
+        }
+        IpNextHeaderProtocols::Tcp => {
+            let tcp = TcpPacket::new(data)?;
+            let segment = data.get(usize::from(tcp.get_data_offset()) * 4..)?;
+            let len = usize::from(u16::from_be_bytes([*segment.first()?, *segment.get(1)?]));
+            Some(DnsPayload {
+                transport: Transport::Tcp,
+                src,
+                sport: tcp.get_source(),
+                dest,
+                dport: tcp.get_destination(),
+                vlans: Vec::new(),
+                interface: None,
+                tunnels: Vec::new(),
+                payload: segment.get(2..2 + len)?,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// GRE (RFC 2784, RFC 2890) carrying IP, Ethernet or ERSPAN. ERSPAN type
+/// I has no header and type II is told apart by the sequence number.
+fn gre_payload(src: IpAddr, dest: IpAddr, data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let header = data.get(..4)?;
+    let flags = u16::from_be_bytes([header[0], header[1]]);
+    // Version 1 is the enhanced GRE of PPTP.
+    if flags & 0x7 != 0 {
+        return None;
+    }
+    let protocol = u16::from_be_bytes([header[2], header[3]]);
+    let mut tunnel = Tunnel::new(TunnelKind::Gre, src, dest);
+    let mut offset = 4;
+    if flags & 0x8000 != 0 {
+        offset += 4;
+    }
+    if flags & 0x2000 != 0 {
+        let key = data.get(offset..offset + 4)?;
+        tunnel.key = Some(u32::from_be_bytes([key[0], key[1], key[2], key[3]]));
+        offset += 4;
+    }
+    let sequenced = flags & 0x1000 != 0;
+    if sequenced {
+        offset += 4;
+    }
+    let data = data.get(offset..)?;
+    let session_id = |header: &[u8]| u16::from_be_bytes([header[2], header[3]]) & 0x3ff;
+    let payload = match protocol {
+        // Transparent Ethernet bridging.
+        0x6558 => ethernet_payload(data, depth)?,
+        0x88be if !sequenced => {
+            tunnel.kind = TunnelKind::Erspan(1);
+            ethernet_payload(data, depth)?
+        }
+        0x88be => {
+            let header = data.get(..8)?;
+            tunnel.kind = TunnelKind::Erspan(2);
+            tunnel.session_id = Some(session_id(header));
+            ethernet_payload(&data[8..], depth)?
+        }
+        0x22eb => {
+            let header = data.get(..12)?;
+            tunnel.kind = TunnelKind::Erspan(3);
+            tunnel.session_id = Some(session_id(header));
+            // The O flag announces an 8 byte platform specific subheader.
+            let len = if header[11] & 1 != 0 { 20 } else { 12 };
+            ethernet_payload(data.get(len..)?, depth)?
+        }
+        protocol => ethertype_payload(EtherType(protocol), data, depth)?,
+    };
+    Some(payload.tunneled(tunnel))
+}
+
+/// VXLAN (RFC 7348): the I flag and the network identifier, then an
+/// Ethernet frame.
+fn vxlan_payload(src: IpAddr, dest: IpAddr, data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let header = data.get(..8)?;
+    if header[0] & 0x08 == 0 {
+        return None;
+    }
+    let payload = ethernet_payload(&data[8..], depth)?;
+    let tunnel = Tunnel {
+        vni: Some(u32::from_be_bytes([0, header[4], header[5], header[6]])),
+        ..Tunnel::new(TunnelKind::Vxlan, src, dest)
+    };
+    Some(payload.tunneled(tunnel))
+}
+
+/// GENEVE (RFC 8926): the network identifier, variable length options and
+/// the protocol type of the payload.
+fn geneve_payload(src: IpAddr, dest: IpAddr, data: &[u8], depth: usize) -> Option<DnsPayload<'_>> {
+    let header = data.get(..8)?;
+    if header[0] >> 6 != 0 {
+        return None;
+    }
+    let options_len = usize::from(header[0] & 0x3f) * 4;
+    let data = data.get(8 + options_len..)?;
+    let payload = match u16::from_be_bytes([header[2], header[3]]) {
+        0x6558 => ethernet_payload(data, depth)?,
+        protocol => ethertype_payload(EtherType(protocol), data, depth)?,
+    };
+    let tunnel = Tunnel {
+        vni: Some(u32::from_be_bytes([0, header[4], header[5], header[6]])),
+        ..Tunnel::new(TunnelKind::Geneve, src, dest)
+    };
+    Some(payload.tunneled(tunnel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DNS_QUERY: &[u8] =
+        b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x1c\x00\x01";
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        udp_to(53, payload)
+    }
+
+    fn udp_to(port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = vec![0xc0, 0x00];
+        udp.extend_from_slice(&port.to_be_bytes());
+        udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        udp
+    }
+
+    fn ipv4(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut ipv4 = vec![0x45, 0];
+        ipv4.extend_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+        ipv4.extend_from_slice(&[0, 0, 0, 0, 64, protocol, 0, 0]);
+        ipv4.extend_from_slice(&[192, 0, 2, 1, 192, 0, 2, 2]);
+        ipv4.extend_from_slice(payload);
+        ipv4
+    }
+
+    fn ethernet(packet: &[u8]) -> Vec<u8> {
+        let mut ethernet = vec![0; 12];
+        ethernet.extend_from_slice(&[0x86, 0xdd]);
+        ethernet.extend_from_slice(packet);
+        ethernet
+    }
+
+    fn ipv6(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut ipv6 = vec![0x60, 0, 0, 0];
+        ipv6.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        ipv6.extend_from_slice(&[next_header, 64]);
+        for addr in ["2001:db8::1", "2001:db8::53"] {
+            let addr: std::net::Ipv6Addr = addr.parse().unwrap();
+            ipv6.extend_from_slice(&addr.octets());
+        }
+        ipv6.extend_from_slice(payload);
+        ipv6
+    }
+
+    #[test]
+    fn test_ipv6_extension_headers() {
+        // Hop-by-hop options, then the first fragment of a UDP datagram.
+        let mut headers = vec![44, 0, 5, 2, 0, 0, 1, 0];
+        headers.extend_from_slice(&[17, 0, 0, 1, 0, 0, 0, 42]);
+        headers.extend_from_slice(&udp(DNS_QUERY));
+        let packet = ipv6(0, &headers);
+        let dns = ipv6_payload(&packet, 0).unwrap();
+        assert_eq!(Transport::Udp, dns.transport);
+        assert_eq!("2001:db8::1", dns.src.to_string());
+        assert_eq!((49152, 53), (dns.sport, dns.dport));
+        assert_eq!(DNS_QUERY, dns.payload);
+
+        // A later fragment has no transport header.
+        let mut fragment = vec![17, 0, 0x05, 0xa8, 0, 0, 0, 42];
+        fragment.extend_from_slice(&udp(DNS_QUERY));
+        assert_eq!(None, ipv6_payload(&ipv6(44, &fragment), 0));
+    }
+
+    #[test]
+    fn test_ipv4_fragments() {
+        let mut packet = ipv4(17, &udp(DNS_QUERY));
+        assert!(ip_payload(&packet).is_some());
+        // A later fragment, then a header length below 20 bytes.
+        packet[6] = 0x00;
+        packet[7] = 0xb9;
+        assert_eq!(None, ip_payload(&packet));
+        packet[7] = 0;
+        packet[0] = 0x44;
+        assert_eq!(None, ip_payload(&packet));
+    }
+
+    #[test]
+    fn test_tcp_message() {
+        let mut segment = vec![0xc0, 0x00, 0x00, 0x35, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18];
+        segment.extend_from_slice(&[0xff, 0xff, 0, 0, 0, 0]);
+        segment.extend_from_slice(&(DNS_QUERY.len() as u16).to_be_bytes());
+        segment.extend_from_slice(DNS_QUERY);
+        let packet = ipv6(6, &segment);
+        let dns = ipv6_payload(&packet, 0).unwrap();
+        assert_eq!(Transport::Tcp, dns.transport);
+        assert_eq!(DNS_QUERY, dns.payload);
+        assert_eq!(
+            None,
+            ipv6_payload(&ipv6(6, &segment[..segment.len() - 1]), 0)
+        );
+    }
+
+    #[test]
+    fn test_decapsulation() {
+        let packet = ipv6(17, &udp(DNS_QUERY));
+        // QinQ with outer VLAN 100 and inner VLAN 200.
+        let mut qinq = vec![0x00, 0x64, 0x81, 0x00, 0x20, 0xc8, 0x86, 0xdd];
+        qinq.extend_from_slice(&packet);
+        let dns = ethertype_payload(EtherTypes::PBridge, &qinq, 0).unwrap();
+        assert_eq!(vec![100, 200], dns.vlans);
+        assert_eq!(DNS_QUERY, dns.payload);
+
+        // Two MPLS labels, then PPPoE carrying IPv6.
+        let mut mpls = vec![0x00, 0x01, 0x00, 0x40, 0x00, 0x02, 0x01, 0x40];
+        mpls.extend_from_slice(&packet);
+        let dns = ethertype_payload(EtherTypes::Mpls, &mpls, 0).unwrap();
+        assert_eq!(DNS_QUERY, dns.payload);
+        let mut pppoe = vec![0x11, 0x00, 0x12, 0x34, 0x00, 0x00, 0x00, 0x57];
+        pppoe.extend_from_slice(&packet);
+        let dns = ethertype_payload(EtherTypes::PppoeSession, &pppoe, 0).unwrap();
+        assert!(dns.vlans.is_empty());
+        assert_eq!(DNS_QUERY, dns.payload);
+    }
+
+    #[test]
+    fn test_link_types() {
+        let packet = ipv6(17, &udp(DNS_QUERY));
+        let linked = |header: &[u8]| [header, &packet[..]].concat();
+
+        let sll2 = linked(&[
+            0x86, 0xdd, 0, 0, 0, 0, 0, 3, 0, 1, 4, 6, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        let dns = link_payload(Linktype::LINUX_SLL2, &sll2).unwrap();
+        assert_eq!(Some(3), dns.interface);
+        assert_eq!(DNS_QUERY, dns.payload);
+
+        let sll = linked(&[0, 4, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0x86, 0xdd]);
+        assert_eq!(
+            None,
+            link_payload(Linktype::LINUX_SLL, &sll).unwrap().interface
+        );
+        assert!(link_payload(Linktype::RAW, &packet).is_some());
+        assert!(link_payload(Linktype::NULL, &linked(&[30, 0, 0, 0])).is_some());
+        assert!(link_payload(Linktype::LOOP, &linked(&[0, 0, 0, 24])).is_some());
+        assert_eq!(None, link_payload(Linktype::LOOP, &linked(&[0, 0, 0, 7])));
+    }
+
+    #[test]
+    fn test_radiotap() {
+        let mut frame = vec![0, 0, 8, 0, 0, 0, 0, 0];
+        // QoS data frame from the distribution system.
+        frame.extend_from_slice(&[0x88, 0x02]);
+        frame.extend_from_slice(&[0; 24]);
+        frame.extend_from_slice(&[0xaa, 0xaa, 0x03, 0, 0, 0, 0x86, 0xdd]);
+        frame.extend_from_slice(&ipv6(17, &udp(DNS_QUERY)));
+        let dns = link_payload(Linktype::IEEE802_11_RADIOTAP, &frame).unwrap();
+        assert_eq!(DNS_QUERY, dns.payload);
+
+        // Protected frames can not be decoded.
+        frame[9] |= 0x40;
+        assert_eq!(None, link_payload(Linktype::IEEE802_11_RADIOTAP, &frame));
+    }
+    #[test]
+    fn test_gre() {
+        let frame = ethernet(&ipv6(17, &udp(DNS_QUERY)));
+        // GRE with a key carrying transparent Ethernet bridging.
+        let mut gre = vec![0x20, 0, 0x65, 0x58, 0, 0, 0, 5];
+        gre.extend_from_slice(&frame);
+        let packet = ipv4(47, &gre);
+        let dns = ip_payload(&packet).unwrap();
+        assert_eq!(DNS_QUERY, dns.payload);
+        assert_eq!("GRE 192.0.2.1->192.0.2.2 key=5", dns.tunnels[0].to_string());
+
+        // ERSPAN type II, session 7.
+        let mut erspan = vec![0x10, 0, 0x88, 0xbe, 0, 0, 0, 1];
+        erspan.extend_from_slice(&[0x10, 0x64, 0, 7, 0, 0, 0, 0]);
+        erspan.extend_from_slice(&frame);
+        let packet = ipv4(47, &erspan);
+        let dns = ip_payload(&packet).unwrap();
+        assert_eq!(TunnelKind::Erspan(2), dns.tunnels[0].kind);
+        assert_eq!(Some(7), dns.tunnels[0].session_id);
+        assert_eq!(DNS_QUERY, dns.payload);
+
+        // ERSPAN type III with the platform specific subheader.
+        let mut erspan = vec![0, 0, 0x22, 0xeb];
+        erspan.extend_from_slice(&[0x20, 0, 0x03, 0xff, 0, 0, 0, 0, 0, 0, 0, 1]);
+        erspan.extend_from_slice(&[0; 8]);
+        erspan.extend_from_slice(&frame);
+        let packet = ipv4(47, &erspan);
+        let dns = ip_payload(&packet).unwrap();
+        assert_eq!(TunnelKind::Erspan(3), dns.tunnels[0].kind);
+        assert_eq!(Some(1023), dns.tunnels[0].session_id);
+        assert_eq!(DNS_QUERY, dns.payload);
+    }
+
+    #[test]
+    fn test_udp_tunnels() {
+        let frame = ethernet(&ipv6(17, &udp(DNS_QUERY)));
+        let mut vxlan = vec![0x08, 0, 0, 0, 0, 0, 42, 0];
+        vxlan.extend_from_slice(&frame);
+        // GENEVE with one 4 byte option, carrying VXLAN.
+        let mut geneve = vec![0x01, 0, 0x08, 0x00, 0, 0x01, 0, 0, 0, 0, 0, 0];
+        geneve.extend_from_slice(&ipv4(17, &udp_to(4789, &vxlan)));
+        let packet = ipv6(17, &udp_to(6081, &geneve));
+        let dns = ip_payload(&packet).unwrap();
+        assert_eq!(DNS_QUERY, dns.payload);
+        assert_eq!("2001:db8::1", dns.src.to_string());
+        let tunnels: Vec<String> = dns.tunnels.iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            vec![
+                "GENEVE 2001:db8::1->2001:db8::53 vni=256",
+                "VXLAN 192.0.2.1->192.0.2.2 vni=42",
+            ],
+            tunnels
+        );
+
+        // Without the I flag this is just a datagram to port 4789.
+        vxlan[0] = 0;
+        let packet = ipv4(17, &udp_to(4789, &vxlan));
+        let dns = ip_payload(&packet).unwrap();
+        assert!(dns.tunnels.is_empty());
+        assert_eq!(4789, dns.dport);
+    }
+
+    #[test]
+    fn test_tunnel_depth() {
+        let mut packet = ipv4(17, &udp(DNS_QUERY));
+        for _ in 0..MAX_TUNNELS {
+            packet = ipv4(47, &[&[0, 0, 0x08, 0x00], &packet[..]].concat());
+        }
+        assert_eq!(MAX_TUNNELS, ip_payload(&packet).unwrap().tunnels.len());
+        packet = ipv4(47, &[&[0, 0, 0x08, 0x00], &packet[..]].concat());
+        assert_eq!(None, ip_payload(&packet));
+    }
+}